@@ -0,0 +1 @@
+ALTER TABLE questions ADD COLUMN deleted_at TIMESTAMP WITH TIME ZONE;
\ No newline at end of file
@@ -0,0 +1 @@
+DROP INDEX questions_body_search_idx;
@@ -0,0 +1,3 @@
+ALTER TABLE questions DROP COLUMN vote_count;
+
+DROP TABLE votes;
\ No newline at end of file
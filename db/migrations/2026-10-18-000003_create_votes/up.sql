@@ -0,0 +1,11 @@
+CREATE TABLE votes (
+  id SERIAL PRIMARY KEY,
+  question_id INTEGER NOT NULL REFERENCES questions (id) ON DELETE CASCADE,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+  -- who voted, create_users adds the foreign key once there is a users table
+  user_id INTEGER NOT NULL,
+  CONSTRAINT votes_question_id_user_id_key UNIQUE (question_id, user_id)
+);
+
+-- kept in step with the votes table when votes are cast or removed, so the question list can sort on it
+ALTER TABLE questions ADD COLUMN vote_count INTEGER NOT NULL DEFAULT 0;
\ No newline at end of file
@@ -0,0 +1,3 @@
+ALTER TABLE questions DROP COLUMN answered;
+
+DROP TABLE answers;
\ No newline at end of file
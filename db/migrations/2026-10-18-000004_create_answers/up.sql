@@ -0,0 +1,13 @@
+CREATE TABLE answers (
+  id SERIAL PRIMARY KEY,
+  question_id INTEGER NOT NULL REFERENCES questions (id) ON DELETE CASCADE,
+  body TEXT NOT NULL,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+  updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX answers_question_id_idx ON answers (question_id);
+
+SELECT diesel_manage_updated_at('answers');
+
+ALTER TABLE questions ADD COLUMN answered BOOLEAN NOT NULL DEFAULT FALSE;
\ No newline at end of file
@@ -0,0 +1,3 @@
+ALTER TABLE questions DROP COLUMN event_id;
+
+DROP TABLE events;
\ No newline at end of file
@@ -0,0 +1,17 @@
+CREATE TABLE events (
+  id SERIAL PRIMARY KEY,
+  name TEXT NOT NULL,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+  updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+SELECT diesel_manage_updated_at('events');
+
+-- questions asked before events existed are moved into a single event of their own
+INSERT INTO events (name) SELECT 'Default event' WHERE EXISTS (SELECT 1 FROM questions);
+
+ALTER TABLE questions ADD COLUMN event_id INTEGER REFERENCES events (id) ON DELETE CASCADE;
+UPDATE questions SET event_id = (SELECT MIN(id) FROM events);
+ALTER TABLE questions ALTER COLUMN event_id SET NOT NULL;
+
+CREATE INDEX questions_event_id_idx ON questions (event_id);
\ No newline at end of file
@@ -0,0 +1,5 @@
+ALTER TABLE events DROP COLUMN moderated;
+
+ALTER TABLE questions DROP COLUMN status;
+
+DROP TYPE question_status;
\ No newline at end of file
@@ -0,0 +1,7 @@
+CREATE TYPE question_status AS ENUM ('pending', 'open', 'rejected');
+
+ALTER TABLE questions ADD COLUMN status question_status NOT NULL DEFAULT 'open';
+
+CREATE INDEX questions_event_id_status_idx ON questions (event_id, status);
+
+ALTER TABLE events ADD COLUMN moderated BOOLEAN NOT NULL DEFAULT FALSE;
\ No newline at end of file
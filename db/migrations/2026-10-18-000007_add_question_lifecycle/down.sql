@@ -0,0 +1,9 @@
+-- postgres cannot drop enum values, so the type is rebuilt without them
+UPDATE questions SET status = 'open' WHERE status IN ('pinned', 'highlighted', 'answered', 'archived');
+
+ALTER TABLE questions ALTER COLUMN status DROP DEFAULT;
+ALTER TYPE question_status RENAME TO question_status_old;
+CREATE TYPE question_status AS ENUM ('pending', 'open', 'rejected');
+ALTER TABLE questions ALTER COLUMN status TYPE question_status USING status::text::question_status;
+ALTER TABLE questions ALTER COLUMN status SET DEFAULT 'open';
+DROP TYPE question_status_old;
\ No newline at end of file
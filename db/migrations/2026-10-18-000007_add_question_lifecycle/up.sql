@@ -0,0 +1,11 @@
+-- values added with ADD VALUE can't be used until the migration commits, so the type is
+-- rebuilt with them instead, which lets open questions that were already answered move
+-- into the answered status
+ALTER TABLE questions ALTER COLUMN status DROP DEFAULT;
+ALTER TYPE question_status RENAME TO question_status_old;
+CREATE TYPE question_status AS ENUM ('pending', 'open', 'rejected', 'pinned', 'highlighted', 'answered', 'archived');
+ALTER TABLE questions ALTER COLUMN status TYPE question_status USING (
+  CASE WHEN answered AND status = 'open' THEN 'answered' ELSE status::text END
+)::question_status;
+ALTER TABLE questions ALTER COLUMN status SET DEFAULT 'open';
+DROP TYPE question_status_old;
\ No newline at end of file
@@ -0,0 +1,5 @@
+ALTER TABLE votes DROP CONSTRAINT votes_user_id_fkey;
+
+ALTER TABLE questions DROP COLUMN author_id;
+
+DROP TABLE users;
\ No newline at end of file
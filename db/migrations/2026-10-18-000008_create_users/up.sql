@@ -0,0 +1,16 @@
+CREATE TABLE users (
+  id SERIAL PRIMARY KEY,
+  username TEXT NOT NULL UNIQUE,
+  password_hash TEXT NOT NULL,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+  updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+SELECT diesel_manage_updated_at('users');
+
+-- questions asked while signed out, or by deleted users, have no author
+ALTER TABLE questions ADD COLUMN author_id INTEGER REFERENCES users (id) ON DELETE SET NULL;
+CREATE INDEX questions_author_id_idx ON questions (author_id);
+
+-- a user's votes go with them
+ALTER TABLE votes ADD CONSTRAINT votes_user_id_fkey FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE;
\ No newline at end of file
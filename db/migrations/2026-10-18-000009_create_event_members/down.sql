@@ -0,0 +1,3 @@
+DROP TABLE event_members;
+
+DROP TYPE event_role;
\ No newline at end of file
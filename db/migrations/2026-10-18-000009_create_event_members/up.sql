@@ -0,0 +1,14 @@
+CREATE TYPE event_role AS ENUM ('participant', 'moderator', 'host');
+
+CREATE TABLE event_members (
+  event_id INTEGER NOT NULL REFERENCES events (id) ON DELETE CASCADE,
+  user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+  role event_role NOT NULL,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+  updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (event_id, user_id)
+);
+
+SELECT diesel_manage_updated_at('event_members');
+
+CREATE INDEX event_members_user_id_idx ON event_members (user_id);
\ No newline at end of file
@@ -0,0 +1 @@
+DROP TABLE event_messages;
\ No newline at end of file
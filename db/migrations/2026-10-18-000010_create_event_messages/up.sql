@@ -0,0 +1,8 @@
+CREATE TABLE event_messages (
+  seq BIGINT PRIMARY KEY,
+  event_id INTEGER NOT NULL REFERENCES events (id) ON DELETE CASCADE,
+  message TEXT NOT NULL,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX event_messages_event_id_seq_idx ON event_messages (event_id, seq);
\ No newline at end of file
@@ -0,0 +1 @@
+ALTER TABLE event_messages DROP COLUMN moderators_only;
\ No newline at end of file
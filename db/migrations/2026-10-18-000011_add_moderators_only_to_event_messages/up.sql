@@ -0,0 +1 @@
+ALTER TABLE event_messages ADD COLUMN moderators_only BOOLEAN NOT NULL DEFAULT FALSE;
\ No newline at end of file
@@ -0,0 +1 @@
+DROP SEQUENCE event_messages_seq_seq;
\ No newline at end of file
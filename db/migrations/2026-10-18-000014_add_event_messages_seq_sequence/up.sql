@@ -0,0 +1,4 @@
+-- seqs are handed out from here, or at least kept below it, so they never go back even once the
+-- messages that had them are deleted
+CREATE SEQUENCE event_messages_seq_seq MINVALUE 0 OWNED BY event_messages.seq;
+SELECT setval('event_messages_seq_seq', COALESCE(MAX(seq), 0)) FROM event_messages;
\ No newline at end of file
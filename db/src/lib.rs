@@ -4,13 +4,20 @@ extern crate log;
 extern crate diesel;
 
 pub mod models;
+// print_schema imports the enum types into every table, whether it uses them or not
+#[allow(unused_imports)]
 pub mod schema;
 
 use std::env;
+use std::time::Duration;
 
 use diesel::pg::PgConnection;
 use diesel::r2d2::{ConnectionManager, Pool, PooledConnection};
+use diesel::{sql_query, sql_types::Text, RunQueryDsl};
+use native_tls::TlsConnector;
+use postgres_native_tls::MakeTlsConnector;
 use r2d2::Error;
+use r2d2_postgres::postgres::{fallible_iterator::FallibleIterator, Client};
 
 pub type PgPool = Pool<ConnectionManager<PgConnection>>;
 
@@ -29,3 +36,99 @@ pub fn new_pool() -> PgPool {
         .build(manager)
         .expect("failed to create db pool")
 }
+
+/// How long `listen` waits on a notification before checking in with its caller anyway
+const LISTEN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A notification heard by `listen`
+#[derive(Debug)]
+pub struct Notification {
+    pub channel: String,
+    pub payload: String,
+}
+
+impl From<r2d2_postgres::postgres::Notification> for Notification {
+    fn from(notification: r2d2_postgres::postgres::Notification) -> Self {
+        Notification {
+            channel: notification.channel().to_string(),
+            payload: notification.payload().to_string(),
+        }
+    }
+}
+
+/// Runs `LISTEN` on each of `channels`, on a connection of its own, outside the pool since
+/// it's held for good. `wake` is called with no notifications as soon as it is listening,
+/// then with whatever arrives, and with `None` when nothing has for a while. Listening stops
+/// once `wake` returns false, or when the connection fails
+pub fn listen<F>(channels: &[&str], mut wake: F) -> Result<(), errors::Error>
+where
+    F: FnMut(Option<Vec<Notification>>) -> bool,
+{
+    let mut client = connect_listener()?;
+    for channel in channels {
+        client
+            .batch_execute(&format!("LISTEN {}", channel))
+            .map_err(listen_error)?;
+    }
+
+    let mut heard = Some(Vec::new());
+    while wake(heard) {
+        {
+            let mut notifications = client.notifications();
+            heard = notifications
+                .timeout_iter(LISTEN_TIMEOUT)
+                .next()
+                .map_err(listen_error)?
+                .map(|first| vec![first.into()]);
+            // one wake up covers everything that arrived together
+            if let Some(heard) = heard.as_mut() {
+                while let Some(notification) = notifications.iter().next().map_err(listen_error)? {
+                    heard.push(notification.into());
+                }
+            }
+        }
+
+        if client.is_closed() {
+            return Err(errors::Error::InternalServerError(
+                "LISTEN connection closed".to_string(),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Sends `payload` to everything listening on `channel`, once the transaction `conn` is in
+/// commits
+pub fn notify(conn: &PgConnection, channel: &str, payload: &str) -> Result<(), errors::Error> {
+    sql_query("SELECT pg_notify($1, $2)")
+        .bind::<Text, _>(channel)
+        .bind::<Text, _>(payload)
+        .execute(conn)?;
+
+    Ok(())
+}
+
+/// Whether `listen` can connect with `DATABASE_URL`, for checking before relying on it
+pub fn check_listen() -> Result<(), errors::Error> {
+    connect_listener()?;
+
+    Ok(())
+}
+
+// The pool connects through libpq, but this connection is made by the postgres crate, which
+// only takes sslmode disable, prefer (the default) and require. Like libpq, prefer and
+// require encrypt the connection without checking the server's certificate
+fn connect_listener() -> Result<Client, errors::Error> {
+    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let connector = TlsConnector::builder()
+        .danger_accept_invalid_certs(true)
+        .build()
+        .map_err(|err| errors::Error::InternalServerError(err.to_string()))?;
+
+    Client::connect(&database_url, MakeTlsConnector::new(connector)).map_err(listen_error)
+}
+
+fn listen_error(err: r2d2_postgres::postgres::Error) -> errors::Error {
+    errors::Error::InternalServerError(err.to_string())
+}
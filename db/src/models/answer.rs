@@ -0,0 +1,81 @@
+use chrono::{DateTime, Utc};
+use diesel::{
+    BelongingToDsl, Connection, ExpressionMethods, Insertable, PgConnection, QueryDsl, Queryable,
+    RunQueryDsl,
+};
+use serde::{Deserialize, Serialize};
+
+use errors::Error;
+
+use crate::models::{Question, QuestionStatus};
+use crate::schema::answers;
+
+#[derive(Associations, Clone, Debug, Identifiable, Serialize, Deserialize, Queryable)]
+#[belongs_to(Question)]
+pub struct Answer {
+    pub id: i32,
+    pub question_id: i32,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable, Serialize)]
+#[table_name = "answers"]
+pub struct NewAnswer {
+    pub question_id: i32,
+    pub body: String,
+}
+
+impl Answer {
+    pub fn get_all(
+        conn: &PgConnection,
+        event_id: i32,
+        question_id: i32,
+    ) -> Result<Vec<Answer>, Error> {
+        use crate::schema::answers::dsl::{created_at, id};
+
+        let question = Question::find(conn, event_id, question_id)?;
+
+        let all_answers = Answer::belonging_to(&question)
+            .order((created_at, id))
+            .load::<Answer>(conn)?;
+
+        Ok(all_answers)
+    }
+
+    /// Adds an answer to the question and marks the question as answered. Open questions
+    /// move to the answered status as well, the rest stay where they are. The question is
+    /// returned as it is afterwards, along with the status it had before
+    pub fn create(
+        conn: &PgConnection,
+        event_id: i32,
+        question_id: i32,
+        body: &str,
+    ) -> Result<(Answer, Question, QuestionStatus), Error> {
+        use crate::schema::questions::dsl::{answered, questions};
+
+        conn.transaction(|| {
+            // make sure the question hasn't been deleted, answers_question_id_fkey covers the rest
+            let question = Question::find(conn, event_id, question_id)?;
+
+            let answer = diesel::insert_into(answers::table)
+                .values(NewAnswer {
+                    question_id,
+                    body: body.to_string(),
+                })
+                .get_result::<Answer>(conn)?;
+
+            // the question on stage stays pinned, and archived ones stay out of the way
+            if question.status == QuestionStatus::Open {
+                Question::set_status(conn, event_id, question_id, QuestionStatus::Answered)?;
+            }
+
+            let answered_question = diesel::update(questions.find(question_id))
+                .set(answered.eq(true))
+                .get_result::<Question>(conn)?;
+
+            Ok((answer, answered_question, question.status))
+        })
+    }
+}
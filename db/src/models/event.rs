@@ -0,0 +1,71 @@
+use chrono::{DateTime, Utc};
+use diesel::{Connection, Insertable, PgConnection, QueryDsl, Queryable, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+
+use errors::Error;
+
+use crate::models::{EventMember, Role};
+use crate::schema::events;
+
+#[derive(Clone, Debug, Identifiable, Serialize, Deserialize, Queryable)]
+pub struct Event {
+    pub id: i32,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// When set, new questions wait for a moderator to approve them before they are shown
+    pub moderated: bool,
+}
+
+#[derive(Debug, Insertable, Serialize)]
+#[table_name = "events"]
+pub struct NewEvent {
+    pub name: String,
+    pub moderated: bool,
+}
+
+impl Event {
+    pub fn get_all(conn: &PgConnection) -> Result<Vec<Event>, Error> {
+        use crate::schema::events::dsl::{created_at, events, id};
+
+        let all_events = events.order((created_at, id)).load::<Event>(conn)?;
+
+        Ok(all_events)
+    }
+
+    pub fn find(conn: &PgConnection, event_id: i32) -> Result<Event, Error> {
+        use crate::schema::events::dsl::events;
+
+        let event = events.find(event_id).first::<Event>(conn)?;
+
+        Ok(event)
+    }
+
+    pub fn create(conn: &PgConnection, name: &str, moderated: bool) -> Result<Event, Error> {
+        use crate::schema::events::dsl::events;
+
+        let event = diesel::insert_into(events)
+            .values(NewEvent {
+                name: name.to_string(),
+                moderated,
+            })
+            .get_result::<Event>(conn)?;
+
+        Ok(event)
+    }
+
+    /// Creates an event with `host_id` as its host
+    pub fn create_hosted(
+        conn: &PgConnection,
+        name: &str,
+        moderated: bool,
+        host_id: i32,
+    ) -> Result<Event, Error> {
+        conn.transaction(|| {
+            let event = Event::create(conn, name, moderated)?;
+            EventMember::set_role(conn, event.id, host_id, Role::Host)?;
+
+            Ok(event)
+        })
+    }
+}
@@ -0,0 +1,163 @@
+use std::io::Write;
+
+use chrono::{DateTime, Utc};
+use diesel::{
+    deserialize::{self, FromSql},
+    pg::Pg,
+    serialize::{self, IsNull, Output, ToSql},
+    ExpressionMethods, Insertable, OptionalExtension, PgConnection, QueryDsl, Queryable,
+    RunQueryDsl,
+};
+use serde::{Deserialize, Serialize};
+
+use errors::Error;
+
+use crate::models::{Event, User};
+use crate::schema::event_members;
+use crate::schema::sql_types::EventRole;
+
+/// What a user may do within a single event. Roles are ordered, so a host can do
+/// everything a moderator can.
+#[derive(
+    AsExpression,
+    Clone,
+    Copy,
+    Debug,
+    Deserialize,
+    Eq,
+    FromSqlRow,
+    Ord,
+    PartialEq,
+    PartialOrd,
+    Serialize,
+)]
+#[serde(rename_all = "snake_case")]
+#[sql_type = "EventRole"]
+pub enum Role {
+    /// Anyone without a membership takes part as a participant
+    Participant,
+    /// Approves, edits and answers questions
+    Moderator,
+    /// Runs the event and manages its members
+    Host,
+}
+
+impl Role {
+    const ALL: [Role; 3] = [Role::Participant, Role::Moderator, Role::Host];
+
+    pub fn parse(value: &str) -> Result<Role, Error> {
+        Role::ALL
+            .iter()
+            .find(|role| role.as_str() == value)
+            .copied()
+            .ok_or_else(|| {
+                let roles: Vec<&str> = Role::ALL.iter().map(|role| role.as_str()).collect();
+                Error::invalid_field(
+                    "role",
+                    "invalid",
+                    format!(
+                        "Unknown role '{}', expected one of: {}",
+                        value,
+                        roles.join(", ")
+                    ),
+                )
+            })
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Participant => "participant",
+            Role::Moderator => "moderator",
+            Role::Host => "host",
+        }
+    }
+}
+
+impl ToSql<EventRole, Pg> for Role {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        out.write_all(self.as_str().as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<EventRole, Pg> for Role {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        let value = std::str::from_utf8(not_none!(bytes))?;
+        Role::ALL
+            .iter()
+            .find(|role| role.as_str() == value)
+            .copied()
+            .ok_or_else(|| format!("Unrecognized event_role variant {}", value).into())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Queryable)]
+pub struct EventMember {
+    pub event_id: i32,
+    pub user_id: i32,
+    pub role: Role,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "event_members"]
+pub struct NewEventMember {
+    pub event_id: i32,
+    pub user_id: i32,
+    pub role: Role,
+}
+
+impl EventMember {
+    pub fn get_all(conn: &PgConnection, event: i32) -> Result<Vec<EventMember>, Error> {
+        use crate::schema::event_members::dsl::{created_at, event_id, event_members, user_id};
+
+        Event::find(conn, event)?;
+
+        let members = event_members
+            .filter(event_id.eq(event))
+            .order((created_at, user_id))
+            .load::<EventMember>(conn)?;
+
+        Ok(members)
+    }
+
+    /// The role a user holds in an event, participants without a membership have none
+    pub fn role(conn: &PgConnection, event: i32, user: i32) -> Result<Option<Role>, Error> {
+        use crate::schema::event_members::dsl::{event_members, role};
+
+        let member_role = event_members
+            .find((event, user))
+            .select(role)
+            .first::<Role>(conn)
+            .optional()?;
+
+        Ok(member_role)
+    }
+
+    /// Gives a user a role in an event, replacing any role they already had
+    pub fn set_role(
+        conn: &PgConnection,
+        event: i32,
+        user: i32,
+        new_role: Role,
+    ) -> Result<EventMember, Error> {
+        use crate::schema::event_members::dsl::{event_id, event_members, role, user_id};
+
+        Event::find(conn, event)?;
+        User::find(conn, user)?;
+
+        let member = diesel::insert_into(event_members)
+            .values(NewEventMember {
+                event_id: event,
+                user_id: user,
+                role: new_role,
+            })
+            .on_conflict((event_id, user_id))
+            .do_update()
+            .set(role.eq(new_role))
+            .get_result::<EventMember>(conn)?;
+
+        Ok(member)
+    }
+}
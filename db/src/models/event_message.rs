@@ -0,0 +1,149 @@
+use chrono::{DateTime, Utc};
+use diesel::{
+    sql_query, sql_types::BigInt, Connection, ExpressionMethods, Insertable, PgConnection,
+    QueryDsl, Queryable, RunQueryDsl,
+};
+
+use errors::Error;
+
+use crate::notify;
+use crate::schema::event_messages;
+
+// Any fixed key will do, it only has to be the same for every instance
+const PUBLISH_LOCK: i64 = 0x6576_656e_7473;
+
+/// A message that was broadcast to an event's websockets, kept so clients that reconnect can
+/// catch up. `seq` is handed out by `publish`, and `message` is exactly what was sent.
+/// `moderators_only` messages are only replayed to moderators and hosts
+#[derive(Clone, Debug, Queryable)]
+pub struct EventMessage {
+    pub seq: i64,
+    pub event_id: i32,
+    pub message: String,
+    pub created_at: DateTime<Utc>,
+    pub moderators_only: bool,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "event_messages"]
+pub struct NewEventMessage {
+    pub seq: i64,
+    pub event_id: i32,
+    pub message: String,
+    pub moderators_only: bool,
+}
+
+#[derive(QueryableByName)]
+struct Seq {
+    #[sql_type = "BigInt"]
+    seq: i64,
+}
+
+impl EventMessage {
+    /// Saves a message under a seq that was already handed out, raising `last_seq` to it
+    pub fn create(conn: &PgConnection, message: NewEventMessage) -> Result<EventMessage, Error> {
+        conn.transaction(|| {
+            let message = diesel::insert_into(event_messages::table)
+                .values(message)
+                .get_result::<EventMessage>(conn)?;
+
+            sql_query(
+                "SELECT setval('event_messages_seq_seq', GREATEST(last_value, $1)) AS seq \
+                FROM event_messages_seq_seq",
+            )
+            .bind::<BigInt, _>(message.seq)
+            .execute(conn)?;
+
+            Ok(message)
+        })
+    }
+
+    /// The highest seq handed out so far. Unlike the messages, this never goes back, so it
+    /// is where seqs carry on from
+    pub fn last_seq(conn: &PgConnection) -> Result<i64, Error> {
+        let last = sql_query("SELECT last_value AS seq FROM event_messages_seq_seq")
+            .get_result::<Seq>(conn)?;
+
+        Ok(last.seq)
+    }
+
+    /// The last `limit` messages across all events, oldest first
+    pub fn recent(conn: &PgConnection, limit: i64) -> Result<Vec<EventMessage>, Error> {
+        use crate::schema::event_messages::dsl::seq;
+
+        let mut messages = event_messages::table
+            .order(seq.desc())
+            .limit(limit)
+            .load::<EventMessage>(conn)?;
+        messages.reverse();
+
+        Ok(messages)
+    }
+
+    /// Up to `limit` messages after `seq`, oldest first
+    pub fn after(conn: &PgConnection, after: i64, limit: i64) -> Result<Vec<EventMessage>, Error> {
+        use crate::schema::event_messages::dsl::seq;
+
+        let messages = event_messages::table
+            .filter(seq.gt(after))
+            .order(seq.asc())
+            .limit(limit)
+            .load::<EventMessage>(conn)?;
+
+        Ok(messages)
+    }
+
+    /// Saves a message under the next seq, and announces that seq with `NOTIFY` on `channel`
+    /// when there is one, for when more than one instance is listening. `message` is given
+    /// the seq to build the text with. Seqs come from the `event_messages_seq_seq` sequence,
+    /// so one isn't handed out again after its message is deleted, or after a restart. The
+    /// lock is held until commit, so seqs are committed, and heard about, in order. Anything
+    /// older than the last `keep` is pruned along the way
+    pub fn publish<F>(
+        conn: &PgConnection,
+        channel: Option<&str>,
+        event_id: i32,
+        moderators_only: bool,
+        keep: i64,
+        message: F,
+    ) -> Result<EventMessage, Error>
+    where
+        F: FnOnce(i64) -> Result<String, Error>,
+    {
+        conn.transaction(|| {
+            sql_query("SELECT pg_advisory_xact_lock($1)")
+                .bind::<BigInt, _>(PUBLISH_LOCK)
+                .execute(conn)?;
+
+            let next_seq = sql_query("SELECT nextval('event_messages_seq_seq') AS seq")
+                .get_result::<Seq>(conn)?
+                .seq;
+
+            let published = Self::create(
+                conn,
+                NewEventMessage {
+                    seq: next_seq,
+                    event_id,
+                    message: message(next_seq)?,
+                    moderators_only,
+                },
+            )?;
+
+            if let Some(channel) = channel {
+                notify(conn, channel, &next_seq.to_string())?;
+            }
+            Self::prune(conn, next_seq - keep)?;
+
+            Ok(published)
+        })
+    }
+
+    /// Drops everything up to and including `through`, returning how many were removed
+    pub fn prune(conn: &PgConnection, through: i64) -> Result<usize, Error> {
+        use crate::schema::event_messages::dsl::seq;
+
+        let count = diesel::delete(event_messages::table.filter(seq.le(through))).execute(conn)?;
+
+        Ok(count)
+    }
+}
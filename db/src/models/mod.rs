@@ -1,3 +1,19 @@
+mod answer;
+mod event;
+mod event_member;
+mod event_message;
+mod pagination;
 mod question;
+mod question_status;
+mod user;
+mod vote;
 
-pub use self::question::*;
\ No newline at end of file
+pub use self::answer::*;
+pub use self::event::*;
+pub use self::event_member::*;
+pub use self::event_message::*;
+pub use self::pagination::*;
+pub use self::question::*;
+pub use self::question_status::*;
+pub use self::user::*;
+pub use self::vote::*;
@@ -0,0 +1,127 @@
+use serde::{Deserialize, Serialize};
+
+use errors::Error;
+
+pub const DEFAULT_PAGE_SIZE: i64 = 50;
+pub const MAX_PAGE_SIZE: i64 = 100;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SortDirection {
+    Asc,
+    Desc,
+}
+
+impl SortDirection {
+    pub fn parse(value: Option<&str>, default: SortDirection) -> Result<SortDirection, Error> {
+        match value {
+            None => Ok(default),
+            Some("asc") => Ok(SortDirection::Asc),
+            Some("desc") => Ok(SortDirection::Desc),
+            Some(other) => Err(Error::invalid_field(
+                "direction",
+                "invalid",
+                format!("Unknown direction '{}', expected one of: asc, desc", other),
+            )),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SortDirection::Asc => "asc",
+            SortDirection::Desc => "desc",
+        }
+    }
+}
+
+/// Position of the last row a client has seen. Rows are keyed on the sort value plus the id,
+/// so that rows inserted while paging never shift the rows in pages that come after.
+/// `sort` records the ordering the cursor was issued for, as it is meaningless under any other.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct Cursor {
+    pub sort: String,
+    pub value: String,
+    pub id: i32,
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        // serializing a struct of strings & an int cannot fail
+        let json = serde_json::to_vec(self).unwrap();
+        base64::encode_config(json, base64::URL_SAFE_NO_PAD)
+    }
+
+    pub fn decode(token: &str) -> Result<Cursor, Error> {
+        base64::decode_config(token, base64::URL_SAFE_NO_PAD)
+            .ok()
+            .and_then(|json| serde_json::from_slice(&json).ok())
+            .ok_or_else(Cursor::invalid)
+    }
+
+    pub fn invalid() -> Error {
+        Error::invalid_field("cursor", "invalid", "Invalid cursor")
+    }
+}
+
+pub fn validate_limit(limit: Option<i64>) -> Result<i64, Error> {
+    match limit {
+        None => Ok(DEFAULT_PAGE_SIZE),
+        Some(limit) if (1..=MAX_PAGE_SIZE).contains(&limit) => Ok(limit),
+        Some(_) => Err(Error::invalid_field(
+            "limit",
+            "out_of_range",
+            format!("limit must be between 1 and {}", MAX_PAGE_SIZE),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let cursor = Cursor {
+            sort: "body:asc".to_string(),
+            value: "a question?".to_string(),
+            id: 12,
+        };
+
+        assert_eq!(Cursor::decode(&cursor.encode()).unwrap(), cursor);
+    }
+
+    #[test]
+    fn test_cursor_decode_invalid() {
+        assert_eq!(
+            Cursor::decode("not a cursor"),
+            Err(Error::invalid_field("cursor", "invalid", "Invalid cursor"))
+        );
+    }
+
+    #[test]
+    fn test_sort_direction_parse() {
+        assert_eq!(
+            SortDirection::parse(None, SortDirection::Desc),
+            Ok(SortDirection::Desc)
+        );
+        assert_eq!(
+            SortDirection::parse(Some("asc"), SortDirection::Desc),
+            Ok(SortDirection::Asc)
+        );
+        assert_eq!(
+            SortDirection::parse(Some("up"), SortDirection::Asc),
+            Err(Error::invalid_field(
+                "direction",
+                "invalid",
+                "Unknown direction 'up', expected one of: asc, desc"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_validate_limit() {
+        assert_eq!(validate_limit(None), Ok(DEFAULT_PAGE_SIZE));
+        assert_eq!(validate_limit(Some(10)), Ok(10));
+        assert!(validate_limit(Some(0)).is_err());
+        assert!(validate_limit(Some(MAX_PAGE_SIZE + 1)).is_err());
+    }
+}
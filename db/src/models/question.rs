@@ -1,17 +1,32 @@
-use chrono::{DateTime, Utc};
-use diesel::{Insertable, PgConnection, QueryDsl, Queryable, RunQueryDsl};
+use chrono::{DateTime, SecondsFormat, Utc};
+use diesel::{
+    sql_types::{Array, BigInt, Float, Integer, Text},
+    BoolExpressionMethods, ExpressionMethods, Insertable, PgConnection, QueryDsl, Queryable,
+    RunQueryDsl,
+};
 use serde::{Deserialize, Serialize};
 
-use errors::Error;
+use errors::{Error, Validator};
 
-use crate::schema::questions;
+use crate::models::{validate_limit, Cursor, Event, QuestionStatus, SortDirection};
+use crate::schema::{questions, sql_types::QuestionStatus as QuestionStatusType};
 
-#[derive(Clone, Debug, Identifiable, Serialize, Deserialize, Queryable)]
+#[derive(Clone, Debug, Identifiable, Serialize, Deserialize, Queryable, QueryableByName)]
+#[table_name = "questions"]
 pub struct Question {
     pub id: i32,
     pub body: String,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub vote_count: i32,
+    /// Whether it has any answers, which is separate from the answered status so that
+    /// answering the question on stage doesn't take it off
+    pub answered: bool,
+    pub event_id: i32,
+    pub status: QuestionStatus,
+    /// The signed in user who asked the question, if any
+    pub author_id: Option<i32>,
 }
 
 #[derive(Debug, Deserialize, Queryable, Serialize, PartialEq)]
@@ -20,28 +35,541 @@ pub struct QuestionDetails {
     pub body: String,
 }
 
+/// A page of questions. `next_cursor` is `None` once the last page has been reached.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct QuestionPage {
+    pub questions: Vec<Question>,
+    pub next_cursor: Option<String>,
+}
+
+/// A question matching a search, with its `ts_rank` and a `ts_headline` snippet where
+/// matching words are wrapped in `<mark>` tags. The rest of the snippet is HTML escaped, so
+/// it can be rendered as markup whatever the question says.
+#[derive(Debug, Deserialize, QueryableByName, Serialize)]
+pub struct QuestionSearchResult {
+    #[diesel(embed)]
+    pub question: Question,
+    #[sql_type = "Float"]
+    pub rank: f32,
+    #[sql_type = "Text"]
+    pub headline: String,
+}
+
+/// Query parameters accepted when listing questions. Everything is optional, and values
+/// are validated by `Question::get_all` so that bad input comes back as a `BadRequest`,
+/// and so is any parameter not listed here.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct QuestionFilters {
+    pub limit: Option<i64>,
+    pub cursor: Option<String>,
+    pub sort: Option<String>,
+    pub direction: Option<String>,
+    pub created_after: Option<String>,
+    pub created_before: Option<String>,
+    pub status: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SortKey {
+    CreatedAt,
+    UpdatedAt,
+    Body,
+    VoteCount,
+}
+
+impl SortKey {
+    const ALL: [SortKey; 4] = [
+        SortKey::CreatedAt,
+        SortKey::UpdatedAt,
+        SortKey::Body,
+        SortKey::VoteCount,
+    ];
+
+    pub fn parse(value: Option<&str>) -> Result<SortKey, Error> {
+        let value = match value {
+            Some(value) => value,
+            None => return Ok(SortKey::Body),
+        };
+
+        SortKey::ALL
+            .iter()
+            .find(|key| key.as_str() == value)
+            .copied()
+            .ok_or_else(|| {
+                let keys: Vec<&str> = SortKey::ALL.iter().map(|key| key.as_str()).collect();
+                Error::invalid_field(
+                    "sort",
+                    "invalid",
+                    format!(
+                        "Unknown sort '{}', expected one of: {}",
+                        value,
+                        keys.join(", ")
+                    ),
+                )
+            })
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SortKey::CreatedAt => "created_at",
+            SortKey::UpdatedAt => "updated_at",
+            SortKey::Body => "body",
+            SortKey::VoteCount => "vote_count",
+        }
+    }
+
+    fn cursor_value(&self, question: &Question) -> String {
+        match self {
+            SortKey::CreatedAt => question
+                .created_at
+                .to_rfc3339_opts(SecondsFormat::Micros, true),
+            SortKey::UpdatedAt => question
+                .updated_at
+                .to_rfc3339_opts(SecondsFormat::Micros, true),
+            SortKey::Body => question.body.clone(),
+            SortKey::VoteCount => question.vote_count.to_string(),
+        }
+    }
+}
+
+fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|date| date.with_timezone(&Utc))
+}
+
+fn parse_filter_timestamp(name: &str, value: Option<&str>) -> Result<Option<DateTime<Utc>>, Error> {
+    match value {
+        Some(value) => parse_timestamp(value).map(Some).ok_or_else(|| {
+            Error::invalid_field(
+                name,
+                "invalid",
+                format!("{} must be an RFC 3339 timestamp", name),
+            )
+        }),
+        None => Ok(None),
+    }
+}
+
+fn parse_cursor_timestamp(cursor: &Cursor) -> Result<DateTime<Utc>, Error> {
+    parse_timestamp(&cursor.value).ok_or_else(Cursor::invalid)
+}
+
+fn parse_cursor_count(cursor: &Cursor) -> Result<i32, Error> {
+    cursor.value.parse().map_err(|_| Cursor::invalid())
+}
+
+// Orders the query by the given column, falling back to id so that rows sharing a value
+// still have a stable position
+macro_rules! order_by {
+    ($query:expr, $column:expr, $direction:expr) => {
+        match $direction {
+            SortDirection::Asc => $query.order(($column.asc(), id.asc())),
+            SortDirection::Desc => $query.order(($column.desc(), id.desc())),
+        }
+    };
+}
+
+// Keeps only the rows that come after the cursor in the given ordering
+macro_rules! after_cursor {
+    ($query:expr, $column:expr, $value:expr, $cursor_id:expr, $direction:expr) => {{
+        let value = $value;
+        match $direction {
+            SortDirection::Asc => $query.filter(
+                $column
+                    .gt(value.clone())
+                    .or($column.eq(value).and(id.gt($cursor_id))),
+            ),
+            SortDirection::Desc => $query.filter(
+                $column
+                    .lt(value.clone())
+                    .or($column.eq(value).and(id.lt($cursor_id))),
+            ),
+        }
+    }};
+}
+
 #[derive(Debug, Insertable, Serialize)]
 #[table_name = "questions"]
 pub struct NewQuestion {
     pub body: String,
+    pub event_id: i32,
+    pub status: QuestionStatus,
+    pub author_id: Option<i32>,
 }
 
 impl Question {
-    pub fn get_all(conn: &PgConnection) -> Result<Vec<Question>, Error> {
-        use crate::schema::questions::dsl::{body, questions};
+    pub fn get_all(
+        conn: &PgConnection,
+        event: i32,
+        filters: &QuestionFilters,
+    ) -> Result<QuestionPage, Error> {
+        use crate::schema::questions::dsl::{
+            body, created_at, deleted_at, event_id, id, questions, status, updated_at, vote_count,
+        };
+
+        // every filter is checked before failing, so all of the problems come back at once
+        let mut validator = Validator::new();
+        let limit = validator.collect(validate_limit(filters.limit));
+        let sort = validator.collect(SortKey::parse(filters.sort.as_deref()));
+        let direction = validator.collect(SortDirection::parse(
+            filters.direction.as_deref(),
+            SortDirection::Asc,
+        ));
+        let created_after = validator.collect(parse_filter_timestamp(
+            "created_after",
+            filters.created_after.as_deref(),
+        ));
+        let created_before = validator.collect(parse_filter_timestamp(
+            "created_before",
+            filters.created_before.as_deref(),
+        ));
+        let statuses = validator.collect(match &filters.status {
+            Some(value) => QuestionStatus::parse(value).map(|parsed| vec![parsed]),
+            None => Ok(QuestionStatus::VISIBLE.to_vec()),
+        });
+        let cursor = validator.collect(filters.cursor.as_deref().map(Cursor::decode).transpose());
+        validator.finish()?;
+
+        // collect only returns None for values that failed, and finish has passed
+        let (limit, sort, direction) = (limit.unwrap(), sort.unwrap(), direction.unwrap());
+        let (created_after, created_before) = (created_after.unwrap(), created_before.unwrap());
+        let (statuses, cursor) = (statuses.unwrap(), cursor.unwrap());
 
-        let all_questions = questions.order(body).load::<Question>(conn)?;
+        let cursor_sort = format!("{}:{}", sort.as_str(), direction.as_str());
+        if let Some(cursor) = &cursor {
+            if cursor.sort != cursor_sort {
+                return Err(Error::invalid_field(
+                    "cursor",
+                    "cursor_mismatch",
+                    "Cursor does not match the requested sort",
+                ));
+            }
+        }
 
-        Ok(all_questions)
+        Event::find(conn, event)?;
+
+        let mut query = questions
+            .filter(event_id.eq(event))
+            .filter(status.eq_any(statuses))
+            .filter(deleted_at.is_null())
+            .into_boxed();
+
+        if let Some(created_after) = created_after {
+            query = query.filter(created_at.gt(created_after));
+        }
+        if let Some(created_before) = created_before {
+            query = query.filter(created_at.lt(created_before));
+        }
+
+        query = match sort {
+            SortKey::CreatedAt => order_by!(query, created_at, direction),
+            SortKey::UpdatedAt => order_by!(query, updated_at, direction),
+            SortKey::Body => order_by!(query, body, direction),
+            SortKey::VoteCount => order_by!(query, vote_count, direction),
+        };
+
+        if let Some(cursor) = cursor {
+            query = match sort {
+                SortKey::CreatedAt => after_cursor!(
+                    query,
+                    created_at,
+                    parse_cursor_timestamp(&cursor)?,
+                    cursor.id,
+                    direction
+                ),
+                SortKey::UpdatedAt => after_cursor!(
+                    query,
+                    updated_at,
+                    parse_cursor_timestamp(&cursor)?,
+                    cursor.id,
+                    direction
+                ),
+                SortKey::Body => after_cursor!(query, body, cursor.value, cursor.id, direction),
+                SortKey::VoteCount => after_cursor!(
+                    query,
+                    vote_count,
+                    parse_cursor_count(&cursor)?,
+                    cursor.id,
+                    direction
+                ),
+            };
+        }
+
+        // fetch one extra row to know if there is another page after this one
+        let mut page = query.limit(limit + 1).load::<Question>(conn)?;
+
+        let next_cursor = if page.len() as i64 > limit {
+            page.truncate(limit as usize);
+            page.last().map(|question| {
+                Cursor {
+                    sort: cursor_sort,
+                    value: sort.cursor_value(question),
+                    id: question.id,
+                }
+                .encode()
+            })
+        } else {
+            None
+        };
+
+        Ok(QuestionPage {
+            questions: page,
+            next_cursor,
+        })
     }
 
-    pub fn create(conn: &PgConnection, body: &String) -> Result<Question, Error> {
+    /// Full text search over question bodies, most relevant first
+    pub fn search(
+        conn: &PgConnection,
+        event_id: i32,
+        search: &str,
+        limit: Option<i64>,
+    ) -> Result<Vec<QuestionSearchResult>, Error> {
+        if search.trim().is_empty() {
+            return Err(Error::invalid_field("q", "required", "q is required"));
+        }
+        let limit = validate_limit(limit)?;
+
+        Event::find(conn, event_id)?;
+
+        let results = diesel::sql_query(
+            "SELECT questions.*, \
+                ts_rank(to_tsvector('english', body), query) AS rank, \
+                ts_headline( \
+                    'english', \
+                    replace(replace(replace(body, '&', '&amp;'), '<', '&lt;'), '>', '&gt;'), \
+                    query, \
+                    'StartSel=<mark>, StopSel=</mark>' \
+                ) AS headline \
+            FROM questions, plainto_tsquery('english', $1) query \
+            WHERE event_id = $2 AND status = ANY($4) \
+                AND deleted_at IS NULL AND to_tsvector('english', body) @@ query \
+            ORDER BY rank DESC, id \
+            LIMIT $3",
+        )
+        .bind::<Text, _>(search)
+        .bind::<Integer, _>(event_id)
+        .bind::<BigInt, _>(limit)
+        .bind::<Array<QuestionStatusType>, _>(QuestionStatus::VISIBLE.to_vec())
+        .load::<QuestionSearchResult>(conn)?;
+
+        Ok(results)
+    }
+
+    pub fn find(conn: &PgConnection, event: i32, question_id: i32) -> Result<Question, Error> {
+        use crate::schema::questions::dsl::{deleted_at, event_id, questions};
+
+        let question = questions
+            .find(question_id)
+            .filter(event_id.eq(event))
+            .filter(deleted_at.is_null())
+            .first::<Question>(conn)?;
+
+        Ok(question)
+    }
+
+    /// Same as `find`, except pending, rejected and archived questions are not found, for
+    /// when only what participants can see should be reachable
+    pub fn find_visible(
+        conn: &PgConnection,
+        event: i32,
+        question_id: i32,
+    ) -> Result<Question, Error> {
+        let question = Question::find(conn, event, question_id)?;
+        if !question.status.is_visible() {
+            return Err(Error::NotFound("Record not found".into()));
+        }
+
+        Ok(question)
+    }
+
+    /// Questions asked in a moderated event start out pending
+    pub fn create(
+        conn: &PgConnection,
+        event_id: i32,
+        author_id: Option<i32>,
+        body: &String,
+    ) -> Result<Question, Error> {
         use crate::schema::questions::dsl::questions;
 
+        let event = Event::find(conn, event_id)?;
+        let initial_status = if event.moderated {
+            QuestionStatus::Pending
+        } else {
+            QuestionStatus::Open
+        };
+
         let question = diesel::insert_into(questions)
-            .values(NewQuestion { body: body.clone() })
+            .values(NewQuestion {
+                body: body.clone(),
+                event_id,
+                status: initial_status,
+                author_id,
+            })
             .get_result::<Question>(conn)?;
 
         Ok(question)
     }
+
+    pub fn update(
+        conn: &PgConnection,
+        event: i32,
+        question_id: i32,
+        new_body: &str,
+    ) -> Result<Question, Error> {
+        use crate::schema::questions::dsl::{body, deleted_at, event_id, questions};
+
+        let question = diesel::update(
+            questions
+                .find(question_id)
+                .filter(event_id.eq(event))
+                .filter(deleted_at.is_null()),
+        )
+        .set(body.eq(new_body))
+        .get_result::<Question>(conn)?;
+
+        Ok(question)
+    }
+
+    /// Like `update`, but only while the question is still pending, for authors in moderated
+    /// events. A question a moderator has dealt with since is a conflict
+    pub fn update_pending(
+        conn: &PgConnection,
+        event: i32,
+        question_id: i32,
+        new_body: &str,
+    ) -> Result<Question, Error> {
+        use crate::schema::questions::dsl::{body, deleted_at, event_id, questions, status};
+
+        // filtering on the status in the update itself guards against a concurrent approval
+        diesel::update(
+            questions
+                .find(question_id)
+                .filter(event_id.eq(event))
+                .filter(deleted_at.is_null())
+                .filter(status.eq(QuestionStatus::Pending)),
+        )
+        .set(body.eq(new_body))
+        .get_result::<Question>(conn)
+        .map_err(|err| match err {
+            diesel::result::Error::NotFound => {
+                Error::Conflict("Question has already been moderated".to_string())
+            }
+            err => err.into(),
+        })
+    }
+
+    /// Soft deletes the question by stamping deleted_at, so it can be restored later
+    pub fn delete(conn: &PgConnection, event: i32, question_id: i32) -> Result<Question, Error> {
+        use crate::schema::questions::dsl::{deleted_at, event_id, questions};
+
+        let question = diesel::update(
+            questions
+                .find(question_id)
+                .filter(event_id.eq(event))
+                .filter(deleted_at.is_null()),
+        )
+        .set(deleted_at.eq(Some(Utc::now())))
+        .get_result::<Question>(conn)?;
+
+        Ok(question)
+    }
+
+    pub fn restore(conn: &PgConnection, event: i32, question_id: i32) -> Result<Question, Error> {
+        use crate::schema::questions::dsl::{deleted_at, event_id, questions};
+
+        let question = diesel::update(
+            questions
+                .find(question_id)
+                .filter(event_id.eq(event))
+                .filter(deleted_at.is_not_null()),
+        )
+        .set(deleted_at.eq(None::<DateTime<Utc>>))
+        .get_result::<Question>(conn)?;
+
+        Ok(question)
+    }
+
+    /// Approves a pending question, so it is shown to participants
+    pub fn approve(conn: &PgConnection, event: i32, question_id: i32) -> Result<Question, Error> {
+        Question::transition(
+            conn,
+            event,
+            question_id,
+            QuestionStatus::Pending,
+            QuestionStatus::Open,
+        )
+    }
+
+    pub fn reject(conn: &PgConnection, event: i32, question_id: i32) -> Result<Question, Error> {
+        Question::transition(
+            conn,
+            event,
+            question_id,
+            QuestionStatus::Pending,
+            QuestionStatus::Rejected,
+        )
+    }
+
+    /// Moves a question along its lifecycle, e.g. pinning it or archiving it. The question is
+    /// returned as it is afterwards, along with the status it moved from
+    pub fn set_status(
+        conn: &PgConnection,
+        event: i32,
+        question_id: i32,
+        to: QuestionStatus,
+    ) -> Result<(Question, QuestionStatus), Error> {
+        let question = Question::find(conn, event, question_id)?;
+        if !question.status.can_move_to(to) {
+            return Err(Error::invalid_field(
+                "status",
+                "invalid_transition",
+                format!(
+                    "Cannot move question from {} to {}",
+                    question.status.as_str(),
+                    to.as_str()
+                ),
+            ));
+        }
+
+        let moved = Question::transition(conn, event, question_id, question.status, to)?;
+        Ok((moved, question.status))
+    }
+
+    fn transition(
+        conn: &PgConnection,
+        event: i32,
+        question_id: i32,
+        from: QuestionStatus,
+        to: QuestionStatus,
+    ) -> Result<Question, Error> {
+        use crate::schema::questions::dsl::{questions, status};
+
+        let question = Question::find(conn, event, question_id)?;
+        let invalid = || {
+            Error::bad_request(
+                "invalid_transition",
+                format!(
+                    "Question is {}, not {}",
+                    question.status.as_str(),
+                    from.as_str()
+                ),
+            )
+        };
+
+        if question.status != from {
+            return Err(invalid());
+        }
+
+        // filtering on the status again guards against a concurrent transition
+        diesel::update(questions.find(question_id).filter(status.eq(from)))
+            .set(status.eq(to))
+            .get_result::<Question>(conn)
+            .map_err(|err| match err {
+                diesel::result::Error::NotFound => invalid(),
+                err => err.into(),
+            })
+    }
 }
@@ -0,0 +1,143 @@
+use std::io::Write;
+
+use diesel::{
+    deserialize::{self, FromSql},
+    pg::Pg,
+    serialize::{self, IsNull, Output, ToSql},
+};
+use serde::{Deserialize, Serialize};
+
+use errors::Error;
+
+use crate::schema::sql_types::QuestionStatus as QuestionStatusType;
+
+/// Maps to the question_status postgres enum
+#[derive(AsExpression, Clone, Copy, Debug, Deserialize, FromSqlRow, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[sql_type = "QuestionStatusType"]
+pub enum QuestionStatus {
+    /// Waiting on a moderator, only used by moderated events
+    Pending,
+    Open,
+    Rejected,
+    /// The question currently being discussed on stage
+    Pinned,
+    Highlighted,
+    Answered,
+    /// Finished with, hidden from the default listing
+    Archived,
+}
+
+impl QuestionStatus {
+    const ALL: [QuestionStatus; 7] = [
+        QuestionStatus::Pending,
+        QuestionStatus::Open,
+        QuestionStatus::Rejected,
+        QuestionStatus::Pinned,
+        QuestionStatus::Highlighted,
+        QuestionStatus::Answered,
+        QuestionStatus::Archived,
+    ];
+
+    /// Statuses listed when no status filter is given
+    pub const VISIBLE: [QuestionStatus; 4] = [
+        QuestionStatus::Open,
+        QuestionStatus::Pinned,
+        QuestionStatus::Highlighted,
+        QuestionStatus::Answered,
+    ];
+
+    pub fn parse(value: &str) -> Result<QuestionStatus, Error> {
+        QuestionStatus::ALL
+            .iter()
+            .find(|status| status.as_str() == value)
+            .copied()
+            .ok_or_else(|| {
+                let statuses: Vec<&str> = QuestionStatus::ALL
+                    .iter()
+                    .map(|status| status.as_str())
+                    .collect();
+                Error::invalid_field(
+                    "status",
+                    "invalid",
+                    format!(
+                        "Unknown status '{}', expected one of: {}",
+                        value,
+                        statuses.join(", ")
+                    ),
+                )
+            })
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QuestionStatus::Pending => "pending",
+            QuestionStatus::Open => "open",
+            QuestionStatus::Rejected => "rejected",
+            QuestionStatus::Pinned => "pinned",
+            QuestionStatus::Highlighted => "highlighted",
+            QuestionStatus::Answered => "answered",
+            QuestionStatus::Archived => "archived",
+        }
+    }
+
+    pub fn is_visible(self) -> bool {
+        QuestionStatus::VISIBLE.contains(&self)
+    }
+
+    /// Whether a host may move a question from this status to `to`. Moderation is
+    /// handled separately by approve and reject, so pending and rejected questions
+    /// can't be moved here.
+    pub fn can_move_to(self, to: QuestionStatus) -> bool {
+        use QuestionStatus::*;
+
+        match (self, to) {
+            (Pending, _) | (Rejected, _) | (_, Pending) | (_, Rejected) => false,
+            (from, to) if from == to => false,
+            (Archived, to) => to == Open,
+            _ => true,
+        }
+    }
+}
+
+impl ToSql<QuestionStatusType, Pg> for QuestionStatus {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        out.write_all(self.as_str().as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<QuestionStatusType, Pg> for QuestionStatus {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        let value = std::str::from_utf8(not_none!(bytes))?;
+        QuestionStatus::ALL
+            .iter()
+            .find(|status| status.as_str() == value)
+            .copied()
+            .ok_or_else(|| format!("Unrecognized question_status variant {}", value).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::QuestionStatus::{self, *};
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(QuestionStatus::parse("pinned").unwrap(), Pinned);
+        assert!(QuestionStatus::parse("closed").is_err());
+    }
+
+    #[test]
+    fn test_can_move_to() {
+        assert!(Open.can_move_to(Pinned));
+        assert!(Pinned.can_move_to(Highlighted));
+        assert!(Answered.can_move_to(Archived));
+        assert!(Archived.can_move_to(Open));
+
+        assert!(!Archived.can_move_to(Pinned));
+        assert!(!Open.can_move_to(Open));
+        assert!(!Pending.can_move_to(Open));
+        assert!(!Open.can_move_to(Rejected));
+    }
+}
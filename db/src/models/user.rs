@@ -0,0 +1,85 @@
+use argon2::{Config, Variant};
+use chrono::{DateTime, Utc};
+use diesel::{ExpressionMethods, Insertable, PgConnection, QueryDsl, Queryable, RunQueryDsl};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use errors::Error;
+
+use crate::schema::users;
+
+#[derive(Clone, Debug, Identifiable, Serialize, Deserialize, Queryable)]
+pub struct User {
+    pub id: i32,
+    pub username: String,
+    #[serde(skip)]
+    pub password_hash: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "users"]
+pub struct NewUser {
+    pub username: String,
+    pub password_hash: String,
+}
+
+fn hash_password(password: &str) -> Result<String, Error> {
+    let salt: [u8; 16] = rand::thread_rng().gen();
+    let config = Config {
+        variant: Variant::Argon2id,
+        ..Config::default()
+    };
+
+    argon2::hash_encoded(password.as_bytes(), &salt, &config).map_err(|err| {
+        error!("Failed to hash password - {}", err);
+        Error::InternalServerError("Failed to hash password".into())
+    })
+}
+
+impl User {
+    pub fn find(conn: &PgConnection, user_id: i32) -> Result<User, Error> {
+        use crate::schema::users::dsl::users;
+
+        let user = users.find(user_id).first::<User>(conn)?;
+
+        Ok(user)
+    }
+
+    pub fn create(conn: &PgConnection, username: &str, password: &str) -> Result<User, Error> {
+        use crate::schema::users::dsl::users;
+
+        let user = diesel::insert_into(users)
+            .values(NewUser {
+                username: username.to_string(),
+                password_hash: hash_password(password)?,
+            })
+            .get_result::<User>(conn)?;
+
+        Ok(user)
+    }
+
+    /// Looks up a user by their credentials. An unknown username and a wrong password
+    /// both come back as `Unauthorized`, so callers can't tell which one was wrong
+    pub fn authenticate(conn: &PgConnection, name: &str, password: &str) -> Result<User, Error> {
+        use crate::schema::users::dsl::{username, users};
+
+        let user = match users.filter(username.eq(name)).first::<User>(conn) {
+            Ok(user) => user,
+            Err(diesel::result::Error::NotFound) => return Err(Error::Unauthorized),
+            Err(err) => return Err(err.into()),
+        };
+
+        match argon2::verify_encoded(&user.password_hash, password.as_bytes()) {
+            Ok(true) => Ok(user),
+            Ok(false) => Err(Error::Unauthorized),
+            Err(err) => {
+                error!("Failed to verify password - {}", err);
+                Err(Error::InternalServerError(
+                    "Failed to verify password".into(),
+                ))
+            }
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use chrono::{DateTime, Utc};
+use diesel::{
+    Connection, ExpressionMethods, Insertable, PgConnection, QueryDsl, Queryable, RunQueryDsl,
+};
+use serde::{Deserialize, Serialize};
+
+use errors::Error;
+
+use crate::models::{Question, User};
+use crate::schema::votes;
+
+#[derive(Associations, Clone, Debug, Identifiable, Serialize, Deserialize, Queryable)]
+#[belongs_to(Question)]
+#[belongs_to(User)]
+pub struct Vote {
+    pub id: i32,
+    pub question_id: i32,
+    pub created_at: DateTime<Utc>,
+    pub user_id: i32,
+}
+
+#[derive(Debug, Insertable, Serialize)]
+#[table_name = "votes"]
+pub struct NewVote {
+    pub question_id: i32,
+    pub user_id: i32,
+}
+
+impl Vote {
+    /// Records a vote, returning the question with its new vote count.
+    /// A user voting twice for the same question violates votes_question_id_user_id_key.
+    pub fn create(
+        conn: &PgConnection,
+        event_id: i32,
+        question_id: i32,
+        user_id: i32,
+    ) -> Result<Question, Error> {
+        use crate::schema::questions::dsl::{questions, vote_count};
+
+        conn.transaction(|| {
+            // make sure the question exists, and is one participants can see
+            Question::find_visible(conn, event_id, question_id)?;
+
+            diesel::insert_into(votes::table)
+                .values(NewVote {
+                    question_id,
+                    user_id,
+                })
+                .execute(conn)?;
+
+            let question = diesel::update(questions.find(question_id))
+                .set(vote_count.eq(vote_count + 1))
+                .get_result::<Question>(conn)?;
+
+            Ok(question)
+        })
+    }
+
+    /// Removes a vote, returning the question with its new vote count
+    pub fn delete(
+        conn: &PgConnection,
+        event_id: i32,
+        question_id: i32,
+        user_id: i32,
+    ) -> Result<Question, Error> {
+        use crate::schema::questions::dsl::{questions, vote_count};
+
+        conn.transaction(|| {
+            Question::find_visible(conn, event_id, question_id)?;
+
+            let deleted = diesel::delete(
+                votes::table
+                    .filter(votes::question_id.eq(question_id))
+                    .filter(votes::user_id.eq(user_id)),
+            )
+            .execute(conn)?;
+
+            if deleted == 0 {
+                return Err(Error::NotFound("Vote not found".to_string()));
+            }
+
+            let question = diesel::update(questions.find(question_id))
+                .set(vote_count.eq(vote_count - 1))
+                .get_result::<Question>(conn)?;
+
+            Ok(question)
+        })
+    }
+}
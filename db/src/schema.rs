@@ -1,8 +1,123 @@
+pub mod sql_types {
+    #[derive(QueryId, SqlType)]
+    #[postgres(type_name = "event_role")]
+    pub struct EventRole;
+
+    #[derive(QueryId, SqlType)]
+    #[postgres(type_name = "question_status")]
+    pub struct QuestionStatus;
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::schema::sql_types::*;
+
+    answers (id) {
+        id -> Int4,
+        question_id -> Int4,
+        body -> Text,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::schema::sql_types::*;
+
+    event_members (event_id, user_id) {
+        event_id -> Int4,
+        user_id -> Int4,
+        role -> EventRole,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::schema::sql_types::*;
+
+    event_messages (seq) {
+        seq -> Int8,
+        event_id -> Int4,
+        message -> Text,
+        created_at -> Timestamptz,
+        moderators_only -> Bool,
+    }
+}
+
 table! {
+    use diesel::sql_types::*;
+    use crate::schema::sql_types::*;
+
+    events (id) {
+        id -> Int4,
+        name -> Text,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        moderated -> Bool,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::schema::sql_types::*;
+
     questions (id) {
         id -> Int4,
         body -> Text,
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
+        deleted_at -> Nullable<Timestamptz>,
+        vote_count -> Int4,
+        answered -> Bool,
+        event_id -> Int4,
+        status -> QuestionStatus,
+        author_id -> Nullable<Int4>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::schema::sql_types::*;
+
+    users (id) {
+        id -> Int4,
+        username -> Text,
+        password_hash -> Text,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::schema::sql_types::*;
+
+    votes (id) {
+        id -> Int4,
+        question_id -> Int4,
+        created_at -> Timestamptz,
+        user_id -> Int4,
     }
 }
+
+joinable!(answers -> questions (question_id));
+joinable!(event_members -> events (event_id));
+joinable!(event_messages -> events (event_id));
+joinable!(event_members -> users (user_id));
+joinable!(questions -> events (event_id));
+joinable!(questions -> users (author_id));
+joinable!(votes -> questions (question_id));
+joinable!(votes -> users (user_id));
+
+allow_tables_to_appear_in_same_query!(
+    answers,
+    event_members,
+    event_messages,
+    events,
+    questions,
+    users,
+    votes,
+);
@@ -3,87 +3,241 @@ extern crate log;
 
 use actix_web::{
     error::{BlockingError, ResponseError},
-    Error as ActixError, HttpResponse,
+    http::{
+        header::{RETRY_AFTER, WWW_AUTHENTICATE},
+        StatusCode,
+    },
+    Error as ActixError, HttpResponse, HttpResponseBuilder,
 };
 use derive_more::Display;
 use diesel::result::{DatabaseErrorKind, Error as DBError};
 use r2d2::Error as PoolError;
 use serde::{Deserialize, Serialize};
 
+mod validation;
+
+pub use self::validation::Validator;
+
+/// Content type of `Error::problem_response`
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+/// Sent with 503s caused by pool timeouts, by then the pool has usually freed up
+const POOL_RETRY_AFTER_SECONDS: u32 = 5;
+
 #[derive(Debug, Display, PartialEq)]
 pub enum Error {
-    BadRequest(String),
+    /// Everything wrong with the request, see `Validator` for collecting more than one
+    #[display(fmt = "BadRequest({:?})", _0)]
+    BadRequest(Vec<ErrorDetail>),
     InternalServerError(String),
     Unauthorized,
     Forbidden,
     NotFound(String),
+    Conflict(String),
+    TooManyRequests(String),
     PoolError(String),
     BlockingError(String),
 }
 
+impl Error {
+    /// A bad request with a single problem that isn't down to one field
+    pub fn bad_request(code: &str, message: impl Into<String>) -> Error {
+        Error::BadRequest(vec![ErrorDetail::new(code, message)])
+    }
+
+    /// A bad request with a single problem in the named field
+    pub fn invalid_field(field: &str, code: &str, message: impl Into<String>) -> Error {
+        Error::BadRequest(vec![ErrorDetail::for_field(field, code, message)])
+    }
+}
+
 impl ResponseError for Error {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Error::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Error::Unauthorized => StatusCode::UNAUTHORIZED,
+            Error::Forbidden => StatusCode::FORBIDDEN,
+            Error::NotFound(_) => StatusCode::NOT_FOUND,
+            Error::Conflict(_) => StatusCode::CONFLICT,
+            Error::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
+            Error::PoolError(_) => StatusCode::SERVICE_UNAVAILABLE,
+            Error::InternalServerError(_) | Error::BlockingError(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+
     fn error_response(&self) -> HttpResponse {
+        self.log();
+        self.response_builder().json(self.body())
+    }
+}
+
+impl Error {
+    /// The same error as an RFC 7807 problem document, for clients that ask for one.
+    /// `instance` is the path of the request that failed
+    pub fn problem_response(&self, instance: &str) -> HttpResponse {
+        self.response_builder()
+            .content_type(PROBLEM_JSON)
+            .json(ProblemDetails::new(self, instance))
+    }
+
+    fn response_builder(&self) -> HttpResponseBuilder {
+        let mut response = HttpResponse::build(self.status_code());
         match self {
-            Error::BadRequest(message) => {
-                let error: ErrorResponse = message.into();
-                HttpResponse::BadRequest().json(error)
+            // sessions are cookie based, see the login route
+            Error::Unauthorized => {
+                response.insert_header((WWW_AUTHENTICATE, r#"Cookie realm="api""#));
             }
-            Error::NotFound(message) => {
-                let error: ErrorResponse = message.into();
-                HttpResponse::NotFound().json(error)
+            Error::PoolError(_) => {
+                response.insert_header((RETRY_AFTER, POOL_RETRY_AFTER_SECONDS.to_string()));
             }
-            Error::Forbidden => {
-                let error: ErrorResponse = "Forbidden".into();
-                HttpResponse::Forbidden().json(error)
+            _ => {}
+        }
+        response
+    }
+
+    fn body(&self) -> ErrorResponse {
+        match self {
+            Error::BadRequest(details) => details.clone().into(),
+            Error::NotFound(message) => ErrorResponse::new("not_found", message),
+            Error::Conflict(message) => ErrorResponse::new("conflict", message),
+            Error::TooManyRequests(message) => ErrorResponse::new("too_many_requests", message),
+            Error::Unauthorized => ErrorResponse::new("unauthorized", "Unauthorized"),
+            Error::Forbidden => ErrorResponse::new("forbidden", "Forbidden"),
+            Error::PoolError(_) => ErrorResponse::new("service_unavailable", "Service Unavailable"),
+            Error::InternalServerError(_) | Error::BlockingError(_) => {
+                ErrorResponse::new("internal_server_error", "Internal Server Error")
             }
-            _ => {
-                error!("Internal server error: {:?}", self);
-                let error: ErrorResponse = "Internal Server Error".into();
-                HttpResponse::InternalServerError().json(error)
+        }
+    }
+
+    /// Logs server side failures, since their messages never reach the client. Already done
+    /// by `error_response`, so only needed when the error is reported some other way
+    pub fn log(&self) {
+        match self {
+            Error::PoolError(message) => error!("Database pool error: {}", message),
+            Error::InternalServerError(_) | Error::BlockingError(_) => {
+                error!("Internal server error: {:?}", self)
             }
+            _ => {}
+        }
+    }
+}
+
+/// One problem with a request. `code` is stable for clients to match on, unlike the
+/// message, and `field` names the request parameter at fault when there is one
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ErrorDetail {
+    pub code: String,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+}
+
+impl ErrorDetail {
+    pub fn new(code: &str, message: impl Into<String>) -> ErrorDetail {
+        ErrorDetail {
+            code: code.to_string(),
+            message: message.into(),
+            field: None,
+        }
+    }
+
+    pub fn for_field(field: &str, code: &str, message: impl Into<String>) -> ErrorDetail {
+        ErrorDetail {
+            field: Some(field.to_string()),
+            ..ErrorDetail::new(code, message)
         }
     }
 }
-// User-friendly error messages
+
+// User-friendly error messages. `errors` only has the messages, and is kept as it was
+// for clients that predate `details`
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ErrorResponse {
     pub errors: Vec<String>,
+    #[serde(default)]
+    pub details: Vec<ErrorDetail>,
 }
 
-impl From<&str> for ErrorResponse {
-    fn from(error: &str) -> Self {
-        ErrorResponse {
-            errors: vec![error.into()],
-        }
+impl ErrorResponse {
+    pub fn new(code: &str, message: &str) -> ErrorResponse {
+        vec![ErrorDetail::new(code, message)].into()
+    }
+}
+
+/// The body `error_response` would send
+impl From<&Error> for ErrorResponse {
+    fn from(error: &Error) -> Self {
+        error.body()
     }
 }
 
-impl From<&String> for ErrorResponse {
-    fn from(error: &String) -> Self {
+impl From<Vec<ErrorDetail>> for ErrorResponse {
+    fn from(details: Vec<ErrorDetail>) -> Self {
         ErrorResponse {
-            errors: vec![error.into()],
+            errors: details
+                .iter()
+                .map(|detail| detail.message.clone())
+                .collect(),
+            details,
         }
     }
 }
 
-impl From<Vec<String>> for ErrorResponse {
-    fn from(error: Vec<String>) -> Self {
-        ErrorResponse { errors: error }
+/// An RFC 7807 problem document. `details` is an extension member carrying the same
+/// entries as `ErrorResponse::details`, so clients can still match on codes and fields
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ProblemDetails {
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+    pub instance: String,
+    #[serde(default)]
+    pub details: Vec<ErrorDetail>,
+}
+
+impl ProblemDetails {
+    pub fn new(error: &Error, instance: &str) -> ProblemDetails {
+        let status = error.status_code();
+        let body = error.body();
+
+        ProblemDetails {
+            // our problems are only distinguished by status, see the `details` codes
+            problem_type: "about:blank".to_string(),
+            title: status.canonical_reason().unwrap_or("Error").to_string(),
+            status: status.as_u16(),
+            detail: body.errors.join(". "),
+            instance: instance.to_string(),
+            details: body.details,
+        }
     }
 }
 
 // Convert DBErrors to our Error type
 impl From<DBError> for Error {
     fn from(error: DBError) -> Error {
-        // Right now we just care about UniqueViolation from diesel
-        // But this would be helpful to easily map errors as our app grows
+        // Constraint violations are caused by the request, so they map to client errors.
+        // A unique violation means the record already exists, and a foreign key violation
+        // means the record being referenced does not. Postgres' details name the columns and
+        // values involved, so they are logged rather than returned
         match error {
             DBError::DatabaseError(kind, info) => {
-                if let DatabaseErrorKind::UniqueViolation = kind {
-                    let message = info.details().unwrap_or_else(|| info.message()).to_string();
-                    return Error::BadRequest(message);
+                let message = info.details().unwrap_or_else(|| info.message());
+                match kind {
+                    DatabaseErrorKind::UniqueViolation => {
+                        warn!("Unique violation {}", message);
+                        Error::Conflict("Already exists".into())
+                    }
+                    DatabaseErrorKind::ForeignKeyViolation => {
+                        warn!("Foreign key violation {}", message);
+                        Error::NotFound("Record not found".into())
+                    }
+                    _ => Error::InternalServerError("Unknown database error".into()),
                 }
-                Error::InternalServerError("Unknown database error".into())
             }
             DBError::NotFound => Error::NotFound("Record not found".into()),
             _ => Error::InternalServerError("Unknown database error".into()),
@@ -110,3 +264,238 @@ impl From<ActixError> for Error {
         Error::InternalServerError(error.to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        body,
+        http::{
+            header::{CONTENT_TYPE, RETRY_AFTER, WWW_AUTHENTICATE},
+            StatusCode,
+        },
+        ResponseError,
+    };
+
+    use diesel::result::{DatabaseErrorInformation, DatabaseErrorKind, Error as DBError};
+
+    use super::{Error, ErrorDetail, ErrorResponse, ProblemDetails, PROBLEM_JSON};
+
+    // What postgres reports for a constraint violation
+    struct ViolationInfo(&'static str);
+
+    impl DatabaseErrorInformation for ViolationInfo {
+        fn message(&self) -> &str {
+            "violates constraint"
+        }
+
+        fn details(&self) -> Option<&str> {
+            Some(self.0)
+        }
+
+        fn hint(&self) -> Option<&str> {
+            None
+        }
+
+        fn table_name(&self) -> Option<&str> {
+            None
+        }
+
+        fn column_name(&self) -> Option<&str> {
+            None
+        }
+
+        fn constraint_name(&self) -> Option<&str> {
+            None
+        }
+    }
+
+    async fn read_body(error: &Error) -> ErrorResponse {
+        let response = error.error_response();
+        let bytes = body::to_bytes(response.into_body()).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    async fn assert_response(error: Error, status: StatusCode, code: &str, message: &str) {
+        let response = error.error_response();
+        assert_eq!(response.status(), status);
+        assert_eq!(error.status_code(), status);
+
+        let body = read_body(&error).await;
+        assert_eq!(body.errors, vec![message]);
+        assert_eq!(body.details, vec![ErrorDetail::new(code, message)]);
+    }
+
+    #[actix_web::test]
+    async fn test_client_error_responses() {
+        assert_response(
+            Error::bad_request("invalid_cursor", "Invalid cursor"),
+            StatusCode::BAD_REQUEST,
+            "invalid_cursor",
+            "Invalid cursor",
+        )
+        .await;
+        assert_response(
+            Error::Forbidden,
+            StatusCode::FORBIDDEN,
+            "forbidden",
+            "Forbidden",
+        )
+        .await;
+        assert_response(
+            Error::NotFound("Record not found".into()),
+            StatusCode::NOT_FOUND,
+            "not_found",
+            "Record not found",
+        )
+        .await;
+        assert_response(
+            Error::Conflict("Already exists".into()),
+            StatusCode::CONFLICT,
+            "conflict",
+            "Already exists",
+        )
+        .await;
+        assert_response(
+            Error::TooManyRequests("Slow down".into()),
+            StatusCode::TOO_MANY_REQUESTS,
+            "too_many_requests",
+            "Slow down",
+        )
+        .await;
+    }
+
+    #[actix_web::test]
+    async fn test_field_error_response() {
+        let error = Error::BadRequest(vec![
+            ErrorDetail::for_field("body", "required", "Body is required"),
+            ErrorDetail::for_field("limit", "out_of_range", "limit must be between 1 and 100"),
+        ]);
+
+        let body = read_body(&error).await;
+        assert_eq!(
+            body.errors,
+            vec!["Body is required", "limit must be between 1 and 100"]
+        );
+        assert_eq!(body.details[0].field.as_deref(), Some("body"));
+        assert_eq!(body.details[1].code, "out_of_range");
+
+        // entries without a field leave it out rather than sending null
+        let json =
+            serde_json::to_value(ErrorResponse::new("not_found", "Record not found")).unwrap();
+        assert!(json["details"][0].get("field").is_none());
+    }
+
+    #[test]
+    fn test_constraint_violations_hide_details() {
+        let error: Error = DBError::DatabaseError(
+            DatabaseErrorKind::ForeignKeyViolation,
+            Box::new(ViolationInfo(
+                "Key (question_id)=(0) is not present in table \"questions\".",
+            )),
+        )
+        .into();
+        assert_eq!(error, Error::NotFound("Record not found".into()));
+
+        let error: Error = DBError::DatabaseError(
+            DatabaseErrorKind::UniqueViolation,
+            Box::new(ViolationInfo(
+                "Key (question_id, user_id)=(1, 2) already exists.",
+            )),
+        )
+        .into();
+        assert_eq!(error, Error::Conflict("Already exists".into()));
+    }
+
+    #[actix_web::test]
+    async fn test_unauthorized_response() {
+        let response = Error::Unauthorized.error_response();
+        assert_eq!(
+            response.headers().get(WWW_AUTHENTICATE).unwrap(),
+            r#"Cookie realm="api""#
+        );
+
+        assert_response(
+            Error::Unauthorized,
+            StatusCode::UNAUTHORIZED,
+            "unauthorized",
+            "Unauthorized",
+        )
+        .await;
+    }
+
+    #[actix_web::test]
+    async fn test_pool_error_response() {
+        let response = Error::PoolError("timed out".into()).error_response();
+        assert_eq!(response.headers().get(RETRY_AFTER).unwrap(), "5");
+
+        // the underlying pool error is logged, not returned
+        assert_response(
+            Error::PoolError("timed out".into()),
+            StatusCode::SERVICE_UNAVAILABLE,
+            "service_unavailable",
+            "Service Unavailable",
+        )
+        .await;
+    }
+
+    #[actix_web::test]
+    async fn test_server_error_responses() {
+        assert_response(
+            Error::InternalServerError("Unknown database error".into()),
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "internal_server_error",
+            "Internal Server Error",
+        )
+        .await;
+        assert_response(
+            Error::BlockingError("Thread blocking error".into()),
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "internal_server_error",
+            "Internal Server Error",
+        )
+        .await;
+    }
+
+    #[actix_web::test]
+    async fn test_problem_response() {
+        let error = Error::BadRequest(vec![
+            ErrorDetail::for_field("body", "required", "Body is required"),
+            ErrorDetail::for_field("limit", "out_of_range", "limit must be between 1 and 100"),
+        ]);
+        let response = error.problem_response("/api/events/1/questions");
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(response.headers().get(CONTENT_TYPE).unwrap(), PROBLEM_JSON);
+
+        let bytes = body::to_bytes(response.into_body()).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["type"], "about:blank");
+        assert_eq!(json["title"], "Bad Request");
+        assert_eq!(json["status"], 400);
+        assert_eq!(
+            json["detail"],
+            "Body is required. limit must be between 1 and 100"
+        );
+        assert_eq!(json["instance"], "/api/events/1/questions");
+
+        let problem: ProblemDetails = serde_json::from_value(json).unwrap();
+        assert_eq!(problem.details[1].field.as_deref(), Some("limit"));
+    }
+
+    #[actix_web::test]
+    async fn test_problem_response_keeps_headers() {
+        let response = Error::Unauthorized.problem_response("/api/auth/me");
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(response.headers().contains_key(WWW_AUTHENTICATE));
+
+        let response = Error::PoolError("timed out".into()).problem_response("/api/events");
+        assert_eq!(response.headers().get(RETRY_AFTER).unwrap(), "5");
+
+        // server errors don't leak their message here either
+        let problem = ProblemDetails::new(
+            &Error::InternalServerError("Unknown database error".into()),
+            "/api/events",
+        );
+        assert_eq!(problem.title, "Internal Server Error");
+        assert_eq!(problem.detail, "Internal Server Error");
+    }
+}
@@ -0,0 +1,105 @@
+use crate::{Error, ErrorDetail};
+
+/// Collects every problem with a request before failing, so that clients can fix them all
+/// in one go rather than one round trip at a time
+///
+/// ```ignore
+/// let mut validator = Validator::new();
+/// validator.required("username", &params.username, "Username is required");
+/// validator.check(params.password.len() >= 8, "password", "too_short", "Password is too short");
+/// validator.finish()?;
+/// ```
+#[derive(Debug, Default)]
+pub struct Validator {
+    details: Vec<ErrorDetail>,
+    fatal: Option<Error>,
+}
+
+impl Validator {
+    pub fn new() -> Validator {
+        Validator::default()
+    }
+
+    /// Records a `required` error for `field` when `value` is empty
+    pub fn required(&mut self, field: &str, value: &str, message: &str) -> &mut Validator {
+        self.check(!value.is_empty(), field, "required", message)
+    }
+
+    /// Records an error for `field` unless `valid` holds
+    pub fn check(
+        &mut self,
+        valid: bool,
+        field: &str,
+        code: &str,
+        message: impl Into<String>,
+    ) -> &mut Validator {
+        if !valid {
+            self.details
+                .push(ErrorDetail::for_field(field, code, message));
+        }
+        self
+    }
+
+    /// Unwraps the result of parsing a single value, keeping its errors for later. Errors
+    /// other than `BadRequest` can't be merged, so the first of those wins in `finish`
+    pub fn collect<T>(&mut self, result: Result<T, Error>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(Error::BadRequest(details)) => {
+                self.details.extend(details);
+                None
+            }
+            Err(error) => {
+                self.fatal.get_or_insert(error);
+                None
+            }
+        }
+    }
+
+    pub fn finish(&mut self) -> Result<(), Error> {
+        if let Some(error) = self.fatal.take() {
+            return Err(error);
+        }
+        if self.details.is_empty() {
+            return Ok(());
+        }
+
+        Err(Error::BadRequest(std::mem::take(&mut self.details)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Validator;
+    use crate::{Error, ErrorDetail};
+
+    #[test]
+    fn test_collects_every_failure() {
+        let mut validator = Validator::new();
+        validator
+            .required("username", "", "Username is required")
+            .check(false, "password", "too_short", "Password is too short")
+            .check(true, "name", "required", "Name is required");
+
+        assert_eq!(
+            validator.finish(),
+            Err(Error::BadRequest(vec![
+                ErrorDetail::for_field("username", "required", "Username is required"),
+                ErrorDetail::for_field("password", "too_short", "Password is too short"),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_collect() {
+        let mut validator = Validator::new();
+
+        assert_eq!(validator.collect::<i32>(Ok(1)), Some(1));
+        assert_eq!(validator.finish(), Ok(()));
+
+        let invalid: Result<i32, Error> = Err(Error::invalid_field("limit", "out_of_range", "bad"));
+        assert_eq!(validator.collect(invalid), None);
+        assert_eq!(validator.collect::<i32>(Err(Error::Forbidden)), None);
+        assert_eq!(validator.finish(), Err(Error::Forbidden));
+    }
+}
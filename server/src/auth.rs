@@ -0,0 +1,203 @@
+use actix_identity::{CookieIdentityPolicy, IdentityService, RequestIdentity};
+use actix_web::{
+    cookie::SameSite,
+    dev::Payload,
+    web::{block, Data},
+    FromRequest, HttpRequest,
+};
+use chrono::{DateTime, TimeZone, Utc};
+use diesel::PgConnection;
+use futures::future::{ready, LocalBoxFuture, Ready};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use db::{
+    get_conn,
+    models::{EventMember, Question, Role},
+    PgPool,
+};
+use errors::Error;
+
+/// The signed in user, read from the identity cookie. Handlers that take a `CurrentUser`
+/// respond with 401 when nobody is signed in, use `Option<CurrentUser>` where signing in
+/// is optional.
+#[derive(Clone, Copy, Debug)]
+pub struct CurrentUser {
+    pub id: i32,
+}
+
+impl CurrentUser {
+    fn from_identity(req: &HttpRequest) -> Result<CurrentUser, Error> {
+        req.get_identity()
+            .and_then(|identity| identity.parse().ok())
+            .map(|id| CurrentUser { id })
+            .ok_or(Error::Unauthorized)
+    }
+}
+
+impl FromRequest for CurrentUser {
+    type Error = Error;
+    type Future = Ready<Result<CurrentUser, Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(CurrentUser::from_identity(req))
+    }
+}
+
+/// Requires a signed in user with at least the moderator role in the event from the
+/// `event_id` segment of the route
+#[derive(Clone, Copy, Debug)]
+pub struct EventModerator;
+
+impl FromRequest for EventModerator {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<EventModerator, Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let member = require_role(req, Role::Moderator);
+
+        Box::pin(async move {
+            member.await?;
+            Ok(EventModerator)
+        })
+    }
+}
+
+/// A signed in user who is a host of the event from the `event_id` segment of the route
+#[derive(Clone, Copy, Debug)]
+pub struct EventHost {
+    pub user: CurrentUser,
+}
+
+impl FromRequest for EventHost {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<EventHost, Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let member = require_role(req, Role::Host);
+
+        Box::pin(async move {
+            let user = member.await?;
+            Ok(EventHost { user })
+        })
+    }
+}
+
+/// Resolves the signed in user's role in the event being requested. Nobody signed in is a
+/// 401, a role below `required` is a 403.
+fn require_role(
+    req: &HttpRequest,
+    required: Role,
+) -> LocalBoxFuture<'static, Result<CurrentUser, Error>> {
+    let user = CurrentUser::from_identity(req);
+    let event_id = req
+        .match_info()
+        .get("event_id")
+        .and_then(|event_id| event_id.parse::<i32>().ok());
+    let pool = req.app_data::<Data<PgPool>>().cloned();
+
+    Box::pin(async move {
+        let user = user?;
+        let event_id = event_id.ok_or_else(|| Error::NotFound("Event not found".to_string()))?;
+        let pool = pool.ok_or_else(|| {
+            Error::InternalServerError("Database pool is not configured".to_string())
+        })?;
+
+        let connection = get_conn(&pool)?;
+        let res = block(move || EventMember::role(&connection, event_id, user.id)).await?;
+
+        match res? {
+            Some(role) if role >= required => Ok(user),
+            _ => Err(Error::Forbidden),
+        }
+    })
+}
+
+/// For what participants can do to their own questions. The author gets through, anyone
+/// else needs at least the moderator role in the question's event, or it's a 403
+pub fn require_author_or_moderator(
+    conn: &PgConnection,
+    user: CurrentUser,
+    question: &Question,
+) -> Result<(), Error> {
+    if question.author_id == Some(user.id) || is_moderator(conn, user, question.event_id)? {
+        Ok(())
+    } else {
+        Err(Error::Forbidden)
+    }
+}
+
+/// Whether the user has at least the moderator role in the event
+pub fn is_moderator(conn: &PgConnection, user: CurrentUser, event_id: i32) -> Result<bool, Error> {
+    let role = EventMember::role(conn, event_id, user.id)?;
+
+    Ok(matches!(role, Some(role) if role >= Role::Moderator))
+}
+
+/// How long a token from `TokenKey::sign` can be used for. Only long enough to open a
+/// websocket with, the socket stays open after it expires
+pub const TOKEN_LIFETIME_SECONDS: i64 = 60;
+
+/// Signs and checks the tokens clients can open a websocket or event stream with, for when
+/// they can't send the session cookie. A token is `{user_id}.{expires}.{signature}`
+#[derive(Clone)]
+pub struct TokenKey {
+    key: Vec<u8>,
+}
+
+impl TokenKey {
+    pub fn new(key: &[u8]) -> Self {
+        TokenKey { key: key.to_vec() }
+    }
+
+    /// A token for `user` that is good until `expires_at`
+    pub fn sign(&self, user: CurrentUser, expires_at: DateTime<Utc>) -> String {
+        let claims = format!("{}.{}", user.id, expires_at.timestamp());
+        let signature = base64::encode_config(
+            self.mac(&claims).finalize().into_bytes(),
+            base64::URL_SAFE_NO_PAD,
+        );
+
+        format!("{}.{}", claims, signature)
+    }
+
+    /// The user a token was signed for. Tampered with, malformed and expired tokens are all
+    /// just unauthorized
+    pub fn verify(&self, token: &str) -> Result<CurrentUser, Error> {
+        let (claims, signature) = token.rsplit_once('.').ok_or(Error::Unauthorized)?;
+        let signature = base64::decode_config(signature, base64::URL_SAFE_NO_PAD)
+            .map_err(|_| Error::Unauthorized)?;
+        self.mac(claims)
+            .verify_slice(&signature)
+            .map_err(|_| Error::Unauthorized)?;
+
+        let (id, expires_at) = claims.split_once('.').ok_or(Error::Unauthorized)?;
+        let expires_at = expires_at.parse().map_err(|_| Error::Unauthorized)?;
+        if Utc.timestamp(expires_at, 0) <= Utc::now() {
+            return Err(Error::Unauthorized);
+        }
+
+        let id = id.parse().map_err(|_| Error::Unauthorized)?;
+        Ok(CurrentUser { id })
+    }
+
+    fn mac(&self, claims: &str) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC takes keys of any size");
+        mac.update(claims.as_bytes());
+        mac
+    }
+}
+
+/// `key` signs the session cookie and must be at least 32 bytes long. The cookie is kept
+/// to same site requests, since the POSTs without a body are simple requests CORS lets any
+/// site send
+pub fn identity_service(key: &[u8], secure: bool) -> IdentityService<CookieIdentityPolicy> {
+    IdentityService::new(
+        CookieIdentityPolicy::new(key)
+            .name("auth")
+            .http_only(true)
+            .same_site(SameSite::Lax)
+            .secure(secure),
+    )
+}
@@ -6,14 +6,16 @@ use std::env;
 use actix::Actor;
 use actix_cors::Cors;
 use actix_rt;
-use actix_web::{http, middleware::Logger, App, HttpServer};
+use actix_web::{http, middleware::Logger, web::Data, App, HttpServer};
 use dotenv::dotenv;
 use env_logger;
 
+mod auth;
+mod problem;
 mod routes;
-mod websocket;
 #[cfg(test)]
 mod tests;
+mod websocket;
 
 #[actix_rt::main]
 async fn main() -> std::io::Result<()> {
@@ -22,7 +24,28 @@ async fn main() -> std::io::Result<()> {
 
     let pool = db::new_pool();
 
-    let server = websocket::Server::new().start();
+    // needed once there is more than one instance, see `Server::fan_out`. LISTEN uses its
+    // own connection, which takes sslmode disable, prefer or require but not verify-ca or
+    // verify-full, so a DATABASE_URL it can't use stops the server here rather than leaving
+    // instances deaf to each other
+    let pg_notify = env::var("PG_NOTIFY")
+        .map(|value| value == "true")
+        .unwrap_or(false);
+    if pg_notify {
+        db::check_listen().expect("PG_NOTIFY is on, but LISTEN could not connect");
+    }
+    let server = websocket::Server::new(pool.clone())
+        .fan_out(pg_notify)
+        .start();
+
+    let secret_key = env::var("SECRET_KEY").expect("SECRET_KEY must be set");
+    let secure_cookies = env::var("COOKIE_SECURE")
+        .map(|value| value != "false")
+        .unwrap_or(true);
+    // problem+json for every error, not just for clients that ask for it
+    let problem_json = env::var("PROBLEM_JSON")
+        .map(|value| value == "true")
+        .unwrap_or(false);
 
     HttpServer::new(move || {
         let cors = Cors::default()
@@ -32,15 +55,23 @@ async fn main() -> std::io::Result<()> {
                 http::header::AUTHORIZATION,
                 http::header::ACCEPT,
                 http::header::CONTENT_TYPE,
+                http::header::HeaderName::from_static("last-event-id"),
             ])
+            .supports_credentials()
             .max_age(3600);
 
         App::new()
+            .wrap(problem::ProblemJson::new(problem_json))
             .wrap(cors)
+            .wrap(auth::identity_service(
+                secret_key.as_bytes(),
+                secure_cookies,
+            ))
             .wrap(Logger::default())
             .wrap(Logger::new("%a %{User-Agent}i"))
             .data(pool.clone())
             .data(server.clone())
+            .app_data(Data::new(auth::TokenKey::new(secret_key.as_bytes())))
             .configure(routes::routes)
     })
     .bind("0.0.0.0:8080")?
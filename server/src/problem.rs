@@ -0,0 +1,185 @@
+use std::future::{ready, Ready};
+
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::Accept,
+    Error as ActixError, HttpMessage,
+};
+use futures_util::future::LocalBoxFuture;
+
+use errors::{Error, PROBLEM_JSON};
+
+/// Middleware that rewrites `errors::Error` responses as RFC 7807 problem documents, when the
+/// client prefers `application/problem+json` or when `always` is set. Anything else, and any
+/// error that isn't ours, goes out as it was.
+///
+/// It has to sit inside the middleware that adds headers (cors, identity), since the response
+/// is replaced rather than edited.
+pub struct ProblemJson {
+    always: bool,
+}
+
+impl ProblemJson {
+    pub fn new(always: bool) -> ProblemJson {
+        ProblemJson { always }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for ProblemJson
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = ActixError> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = ActixError;
+    type Transform = ProblemJsonMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ProblemJsonMiddleware {
+            service,
+            always: self.always,
+        }))
+    }
+}
+
+pub struct ProblemJsonMiddleware<S> {
+    service: S,
+    always: bool,
+}
+
+impl<S, B> Service<ServiceRequest> for ProblemJsonMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = ActixError> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = ActixError;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let wants_problem = self.always || prefers_problem_json(req.get_header::<Accept>());
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let res = fut.await?;
+            if !wants_problem {
+                return Ok(res.map_into_left_body());
+            }
+
+            let problem = res
+                .response()
+                .error()
+                .and_then(|error| error.as_error::<Error>())
+                .map(|error| error.problem_response(res.request().path()));
+
+            match problem {
+                Some(problem) => Ok(res.into_response(problem).map_into_right_body()),
+                None => Ok(res.map_into_left_body()),
+            }
+        })
+    }
+}
+
+// problem+json has to be asked for, and ranked above plain json when both are listed
+fn prefers_problem_json(accept: Option<Accept>) -> bool {
+    let ranked = match accept {
+        Some(accept) => accept.ranked(),
+        None => return false,
+    };
+    let position = |essence: &str| ranked.iter().position(|mime| mime.essence_str() == essence);
+
+    match (position(PROBLEM_JSON), position("application/json")) {
+        (Some(problem), Some(json)) => problem < json,
+        (Some(_), None) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix::Actor;
+    use actix_web::{
+        http::header::{ACCEPT, CONTENT_TYPE},
+        test,
+        web::{self, Bytes},
+        App,
+    };
+
+    use errors::{ErrorResponse, ProblemDetails, PROBLEM_JSON};
+
+    use super::ProblemJson;
+    use crate::auth::identity_service;
+    use crate::routes::routes;
+    use crate::tests::SECRET_KEY;
+    use crate::websocket::Server;
+
+    async fn get_me(always: bool, accept: Option<&str>) -> (String, Bytes) {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(db::new_pool()))
+                .app_data(web::Data::new(Server::new(db::new_pool()).start()))
+                .wrap(ProblemJson::new(always))
+                .wrap(identity_service(SECRET_KEY, false))
+                .configure(routes),
+        )
+        .await;
+
+        let mut req = test::TestRequest::get().uri("/api/auth/me");
+        if let Some(accept) = accept {
+            req = req.insert_header((ACCEPT, accept));
+        }
+        let res = test::call_service(&app, req.to_request()).await;
+        assert_eq!(res.status().as_u16(), 401);
+
+        let content_type = res
+            .headers()
+            .get(CONTENT_TYPE)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        (content_type, test::read_body(res).await)
+    }
+
+    #[actix_rt::test]
+    async fn test_problem_json_when_accepted() {
+        let (content_type, body) = get_me(false, Some("application/problem+json")).await;
+        assert_eq!(content_type, PROBLEM_JSON);
+
+        let problem: ProblemDetails = serde_json::from_slice(&body).unwrap();
+        assert_eq!(problem.problem_type, "about:blank");
+        assert_eq!(problem.title, "Unauthorized");
+        assert_eq!(problem.status, 401);
+        assert_eq!(problem.instance, "/api/auth/me");
+        assert_eq!(problem.details[0].code, "unauthorized");
+    }
+
+    #[actix_rt::test]
+    async fn test_error_response_by_default() {
+        let (content_type, body) = get_me(false, None).await;
+        assert_eq!(content_type, "application/json");
+        let error: ErrorResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(error.errors, vec!["Unauthorized"]);
+
+        // plain json ranked first wins
+        let (content_type, _) = get_me(
+            false,
+            Some("application/json, application/problem+json;q=0.5"),
+        )
+        .await;
+        assert_eq!(content_type, "application/json");
+    }
+
+    #[actix_rt::test]
+    async fn test_problem_json_when_configured() {
+        let (content_type, body) = get_me(true, Some("application/json")).await;
+        assert_eq!(content_type, PROBLEM_JSON);
+        let problem: ProblemDetails = serde_json::from_slice(&body).unwrap();
+        assert_eq!(problem.status, 401);
+    }
+}
@@ -0,0 +1,356 @@
+use actix::Addr;
+use actix_web::{
+    web::{block, Data, Json, Path},
+    Result,
+};
+use serde::{Deserialize, Serialize};
+
+use db::{get_conn, models::Answer, PgPool};
+use errors::{Error, Validator};
+
+use crate::auth::EventModerator;
+use crate::websocket::{ChangedStatus, MessageToClient, PostedAnswer, Server, ServerEvent};
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct CreateAnswerRequest {
+    body: String,
+}
+
+pub async fn create(
+    pool: Data<PgPool>,
+    websocket_srv: Data<Addr<Server>>,
+    path: Path<(i32, i32)>,
+    _moderator: EventModerator,
+    params: Json<CreateAnswerRequest>,
+) -> Result<Json<Answer>, Error> {
+    Validator::new()
+        .required("body", &params.body, "Body is required")
+        .finish()?;
+
+    let connection = get_conn(&pool)?;
+
+    let (event_id, question_id) = path.into_inner();
+    let res =
+        block(move || Answer::create(&connection, event_id, question_id, &params.body)).await?;
+    let (answer, question, previous_status) = res?;
+
+    let posted = PostedAnswer {
+        answer: answer.clone(),
+        question_status: question.status,
+    };
+    let msg = MessageToClient::new(event_id, ServerEvent::NewAnswer(posted));
+    websocket_srv.do_send(msg);
+    if question.status != previous_status {
+        let changed = ChangedStatus {
+            question,
+            previous_status,
+        };
+        let msg = MessageToClient::new(event_id, ServerEvent::StatusChanged(changed));
+        websocket_srv.do_send(msg);
+    }
+
+    Ok(Json(answer))
+}
+
+#[cfg(test)]
+mod tests {
+    use awc::Client;
+    use diesel::{self, RunQueryDsl};
+    use serde_json::json;
+
+    use db::{
+        get_conn,
+        models::{Answer, Event, NewQuestion, Question, QuestionStatus, Role},
+        new_pool,
+        schema::{answers, events, questions, users},
+    };
+    use errors::ErrorResponse;
+
+    use super::CreateAnswerRequest;
+    use crate::tests;
+    use crate::websocket::ServerEvent;
+
+    #[actix_rt::test]
+    pub async fn test_create_answer() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+
+        let question = diesel::insert_into(questions::table)
+            .values(NewQuestion {
+                body: "A question to answer".to_string(),
+                event_id: event.id,
+                status: QuestionStatus::Open,
+                author_id: None,
+            })
+            .get_result::<Question>(&conn)
+            .unwrap();
+        assert!(!question.answered);
+
+        let moderator = tests::sign_in_as(event.id, Role::Moderator).await;
+
+        let srv = tests::get_test_server();
+
+        let client = Client::default();
+        let ws_conn = client
+            .ws(tests::websocket_url(&srv, event.id))
+            .connect()
+            .await
+            .unwrap();
+
+        let mut res = srv
+            .post(format!(
+                "/api/events/{}/questions/{}/answers",
+                event.id, question.id
+            ))
+            .cookie(moderator)
+            .send_json(&CreateAnswerRequest {
+                body: "An answer".to_string(),
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(res.status().as_u16(), 200);
+
+        let answer: Answer = res.json().await.unwrap();
+        assert_eq!(answer.question_id, question.id);
+        assert_eq!(answer.body, "An answer");
+
+        let mut stream = ws_conn.1;
+        let frame = tests::next_websocket_frame(&mut stream).await;
+
+        let data = tests::get_websocket_frame_data(frame);
+        let msg = data.expect("Message was not a string");
+        let posted = match msg.event {
+            ServerEvent::NewAnswer(posted) => posted,
+            event => panic!("expected newanswer, got {:?}", event),
+        };
+        assert_eq!(posted.answer.body, "An answer");
+        assert_eq!(posted.question_status, QuestionStatus::Answered);
+
+        let frame = tests::next_websocket_frame(&mut stream).await;
+        let msg = tests::get_websocket_frame_data(frame).expect("Message was not a string");
+        let changed = match msg.event {
+            ServerEvent::StatusChanged(changed) => changed,
+            event => panic!("expected statuschanged, got {:?}", event),
+        };
+        assert_eq!(changed.previous_status, QuestionStatus::Open);
+        let answered = changed.question;
+        assert_eq!(answered.id, question.id);
+        assert_eq!(answered.status, QuestionStatus::Answered);
+        assert!(answered.answered);
+
+        drop(stream);
+
+        srv.stop().await;
+
+        let question = Question::find(&conn, event.id, question.id).unwrap();
+        assert_eq!(question.status, QuestionStatus::Answered);
+        assert!(question.answered);
+
+        let stored = answers::dsl::answers.load::<Answer>(&conn).unwrap();
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].id, answer.id);
+        assert_eq!(stored[0].question_id, question.id);
+        assert_eq!(stored[0].body, "An answer");
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    pub async fn test_answering_keeps_the_question_on_stage() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+
+        let question = diesel::insert_into(questions::table)
+            .values(NewQuestion {
+                body: "The one being discussed".to_string(),
+                event_id: event.id,
+                status: QuestionStatus::Pinned,
+                author_id: None,
+            })
+            .get_result::<Question>(&conn)
+            .unwrap();
+
+        let moderator = tests::sign_in_as(event.id, Role::Moderator).await;
+        let res: (u16, Answer) = tests::test_post_as(
+            &format!("/api/events/{}/questions/{}/answers", event.id, question.id),
+            CreateAnswerRequest {
+                body: "Answered live".to_string(),
+            },
+            &moderator,
+        )
+        .await;
+        assert_eq!(res.0, 200);
+
+        let answered = Question::find(&conn, event.id, question.id).unwrap();
+        assert_eq!(answered.status, QuestionStatus::Pinned);
+        assert!(answered.answered);
+
+        // it is still answered once it is finished with
+        let (archived, _) =
+            Question::set_status(&conn, event.id, question.id, QuestionStatus::Archived).unwrap();
+        assert!(archived.answered);
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    pub async fn test_answers_to_pending_questions_skip_participants() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", true).unwrap();
+
+        let pending = Question::create(&conn, event.id, None, &"Not yet".to_string()).unwrap();
+        assert_eq!(pending.status, QuestionStatus::Pending);
+        let open = diesel::insert_into(questions::table)
+            .values(NewQuestion {
+                body: "Already approved".to_string(),
+                event_id: event.id,
+                status: QuestionStatus::Open,
+                author_id: None,
+            })
+            .get_result::<Question>(&conn)
+            .unwrap();
+
+        let moderator = tests::sign_in_as(event.id, Role::Moderator).await;
+
+        let srv = tests::get_test_server();
+
+        let client = Client::default();
+        let (_, mut ws_conn) = client
+            .ws(tests::websocket_url(&srv, event.id))
+            .connect()
+            .await
+            .unwrap();
+
+        for question in &[&pending, &open] {
+            let res = srv
+                .post(format!(
+                    "/api/events/{}/questions/{}/answers",
+                    event.id, question.id
+                ))
+                .cookie(moderator.clone())
+                .send_json(&CreateAnswerRequest {
+                    body: format!("An answer to {}", question.body),
+                })
+                .await
+                .unwrap();
+            assert_eq!(res.status().as_u16(), 200);
+        }
+
+        // the participant's first message is the answer to the open question
+        let frame = tests::next_websocket_frame(&mut ws_conn).await;
+        let msg = tests::get_websocket_frame_data(frame).expect("Message was not a string");
+        let posted = match msg.event {
+            ServerEvent::NewAnswer(posted) => posted,
+            event => panic!("expected newanswer, got {:?}", event),
+        };
+        assert_eq!(posted.answer.question_id, open.id);
+
+        drop(ws_conn);
+
+        srv.stop().await;
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    pub async fn test_create_answer_question_not_found() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+
+        let moderator = tests::sign_in_as(event.id, Role::Moderator).await;
+
+        let res: (u16, ErrorResponse) = tests::test_post_as(
+            &format!("/api/events/{}/questions/0/answers", event.id),
+            CreateAnswerRequest {
+                body: "An answer".to_string(),
+            },
+            &moderator,
+        )
+        .await;
+
+        assert_eq!(res.0, 404);
+        assert_eq!(res.1.errors, vec!["Record not found"]);
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    pub async fn test_create_answer_requires_moderator() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+
+        let question = diesel::insert_into(questions::table)
+            .values(NewQuestion {
+                body: "A question to answer".to_string(),
+                event_id: event.id,
+                status: QuestionStatus::Open,
+                author_id: None,
+            })
+            .get_result::<Question>(&conn)
+            .unwrap();
+
+        let route = format!("/api/events/{}/questions/{}/answers", event.id, question.id);
+        let params = CreateAnswerRequest {
+            body: "An answer".to_string(),
+        };
+
+        let res: (u16, ErrorResponse) = tests::test_post(&route, &params).await;
+        assert_eq!(res.0, 401);
+
+        let participant = tests::sign_in_as(event.id, Role::Participant).await;
+        let res: (u16, ErrorResponse) = tests::test_post_as(&route, &params, &participant).await;
+        assert_eq!(res.0, 403);
+        assert_eq!(res.1.errors, vec!["Forbidden"]);
+
+        let answers = answers::dsl::answers.load::<Answer>(&conn).unwrap();
+        assert_eq!(answers.len(), 0);
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    pub async fn test_create_answer_body_required() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+
+        let question = Question::create(&conn, event.id, None, &"Unanswered".to_string()).unwrap();
+        let moderator = tests::sign_in_as(event.id, Role::Moderator).await;
+        let route = format!("/api/events/{}/questions/{}/answers", event.id, question.id);
+
+        let res: (u16, ErrorResponse) = tests::test_post_as(&route, json!({}), &moderator).await;
+        assert_eq!(res.0, 400);
+        assert_eq!(res.1.details[0].code, "invalid_body");
+        assert!(res.1.errors[0].contains("missing field `body`"));
+
+        let res: (u16, ErrorResponse) = tests::test_post_as(
+            &route,
+            CreateAnswerRequest {
+                body: "".to_string(),
+            },
+            &moderator,
+        )
+        .await;
+        assert_eq!(res.0, 400);
+        assert_eq!(res.1.errors, vec!["Body is required"]);
+
+        let answers = answers::dsl::answers.load::<Answer>(&conn).unwrap();
+        assert_eq!(answers.len(), 0);
+        let question = Question::find(&conn, event.id, question.id).unwrap();
+        assert_eq!(question.status, QuestionStatus::Open);
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+}
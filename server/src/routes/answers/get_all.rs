@@ -0,0 +1,109 @@
+use actix_web::{
+    web::{block, Data, Json, Path},
+    Result,
+};
+
+use db::{
+    get_conn,
+    models::{Answer, Question},
+    PgPool,
+};
+use errors::Error;
+
+use crate::auth::EventModerator;
+
+/// Like `questions::get`, the answers to a question participants can't see are only
+/// found for moderators
+pub async fn get_all(
+    pool: Data<PgPool>,
+    path: Path<(i32, i32)>,
+    moderator: Option<EventModerator>,
+) -> Result<Json<Vec<Answer>>, Error> {
+    let connection = get_conn(&pool)?;
+
+    let (event_id, question_id) = path.into_inner();
+    let res = block(move || {
+        if moderator.is_none() {
+            Question::find_visible(&connection, event_id, question_id)?;
+        }
+        Answer::get_all(&connection, event_id, question_id)
+    })
+    .await?;
+    let answers = res?;
+
+    Ok(Json(answers))
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::{self, RunQueryDsl};
+
+    use db::{
+        get_conn,
+        models::{Answer, Event, NewQuestion, Question, QuestionStatus, Role},
+        new_pool,
+        schema::{events, questions, users},
+    };
+    use errors::ErrorResponse;
+
+    use crate::tests;
+
+    #[actix_rt::test]
+    async fn test_get_all_returns_answers() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+
+        let question = diesel::insert_into(questions::table)
+            .values(NewQuestion {
+                body: "one question".to_string(),
+                event_id: event.id,
+                status: QuestionStatus::Open,
+                author_id: None,
+            })
+            .get_result::<Question>(&conn)
+            .unwrap();
+        Answer::create(&conn, event.id, question.id, "first answer").unwrap();
+        Answer::create(&conn, event.id, question.id, "second answer").unwrap();
+
+        let res: (u16, Vec<Answer>) = tests::test_get(&format!(
+            "/api/events/{}/questions/{}/answers",
+            event.id, question.id
+        ))
+        .await;
+        assert_eq!(res.0, 200);
+        let bodies: Vec<&str> = res.1.iter().map(|a| a.body.as_str()).collect();
+        assert_eq!(bodies, vec!["first answer", "second answer"]);
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_get_all_hides_answers_to_pending_questions() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "A moderated event", true).unwrap();
+
+        let question = Question::create(&conn, event.id, None, &"Not yet".to_string()).unwrap();
+        Answer::create(&conn, event.id, question.id, "an early answer").unwrap();
+        let route = format!("/api/events/{}/questions/{}/answers", event.id, question.id);
+
+        let res: (u16, ErrorResponse) = tests::test_get(&route).await;
+        assert_eq!(res.0, 404);
+
+        let moderator = tests::sign_in_as(event.id, Role::Moderator).await;
+        let res: (u16, Vec<Answer>) = tests::test_get_as(&route, &moderator).await;
+        assert_eq!(res.0, 200);
+        assert_eq!(res.1.len(), 1);
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_get_all_question_not_found() {
+        let res: (u16, ErrorResponse) = tests::test_get("/api/events/0/questions/0/answers").await;
+        assert_eq!(res.0, 404);
+        assert_eq!(res.1.errors, vec!["Record not found"]);
+    }
+}
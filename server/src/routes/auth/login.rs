@@ -0,0 +1,102 @@
+use actix_identity::Identity;
+use actix_web::{
+    web::{block, Data, Json},
+    Result,
+};
+use serde::{Deserialize, Serialize};
+
+use db::{get_conn, models::User, PgPool};
+use errors::Error;
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+pub async fn login(
+    pool: Data<PgPool>,
+    identity: Identity,
+    params: Json<Credentials>,
+) -> Result<Json<User>, Error> {
+    let connection = get_conn(&pool)?;
+
+    let res =
+        block(move || User::authenticate(&connection, &params.username, &params.password)).await?;
+    let user = res?;
+
+    identity.remember(user.id.to_string());
+
+    Ok(Json(user))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::cookie::SameSite;
+    use diesel::{self, RunQueryDsl};
+
+    use db::{
+        get_conn,
+        models::User,
+        new_pool,
+        schema::{events, users},
+    };
+    use errors::ErrorResponse;
+
+    use super::Credentials;
+    use crate::tests;
+
+    #[actix_rt::test]
+    async fn test_login() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+
+        let user = User::create(&conn, "host", "correct horse").unwrap();
+
+        let cookie = tests::sign_in("host", "correct horse").await;
+        assert!(cookie.http_only().unwrap_or(false));
+        assert_eq!(cookie.same_site(), Some(SameSite::Lax));
+
+        let res: (u16, User) = tests::test_get_as("/api/auth/me", &cookie).await;
+        assert_eq!(res.0, 200);
+        assert_eq!(res.1.id, user.id);
+        assert_eq!(res.1.username, "host");
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_login_invalid_credentials() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+
+        User::create(&conn, "host", "correct horse").unwrap();
+
+        let res: (u16, ErrorResponse) = tests::test_post(
+            "/api/auth/login",
+            Credentials {
+                username: "host".to_string(),
+                password: "wrong horse".to_string(),
+            },
+        )
+        .await;
+        assert_eq!(res.0, 401);
+        assert_eq!(res.1.errors, vec!["Unauthorized"]);
+
+        let res: (u16, ErrorResponse) = tests::test_post(
+            "/api/auth/login",
+            Credentials {
+                username: "nobody".to_string(),
+                password: "correct horse".to_string(),
+            },
+        )
+        .await;
+        assert_eq!(res.0, 401);
+
+        let res: (u16, ErrorResponse) = tests::test_get("/api/auth/me").await;
+        assert_eq!(res.0, 401);
+
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+}
@@ -0,0 +1,46 @@
+use actix_identity::Identity;
+use actix_web::HttpResponse;
+
+pub async fn logout(identity: Identity) -> HttpResponse {
+    identity.forget();
+
+    HttpResponse::NoContent().finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test;
+    use diesel::{self, RunQueryDsl};
+
+    use db::{get_conn, models::User, new_pool, schema::users};
+
+    use crate::tests;
+
+    #[actix_rt::test]
+    async fn test_logout() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+
+        User::create(&conn, "host", "correct horse").unwrap();
+        let cookie = tests::sign_in("host", "correct horse").await;
+
+        let app = tests::get_service().await;
+        let req = test::TestRequest::post()
+            .uri("/api/auth/logout")
+            .cookie(cookie)
+            .to_request();
+        let res = test::call_service(&app, req).await;
+
+        assert_eq!(res.status().as_u16(), 204);
+
+        // the cookie is cleared by setting it again with an expiry in the past
+        let cleared = res
+            .response()
+            .cookies()
+            .find(|cookie| cookie.name() == "auth")
+            .expect("auth cookie was not cleared");
+        assert_eq!(cleared.value(), "");
+
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+}
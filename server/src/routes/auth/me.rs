@@ -0,0 +1,18 @@
+use actix_web::{
+    web::{block, Data, Json},
+    Result,
+};
+
+use db::{get_conn, models::User, PgPool};
+use errors::Error;
+
+use crate::auth::CurrentUser;
+
+pub async fn me(pool: Data<PgPool>, user: CurrentUser) -> Result<Json<User>, Error> {
+    let connection = get_conn(&pool)?;
+
+    let res = block(move || User::find(&connection, user.id)).await?;
+    let user = res?;
+
+    Ok(Json(user))
+}
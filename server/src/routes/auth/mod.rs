@@ -0,0 +1,11 @@
+mod login;
+mod logout;
+mod me;
+mod register;
+mod ws_token;
+
+pub use self::login::*;
+pub use self::logout::*;
+pub use self::me::*;
+pub use self::register::*;
+pub use self::ws_token::*;
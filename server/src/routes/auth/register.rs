@@ -0,0 +1,146 @@
+use actix_identity::Identity;
+use actix_web::{
+    web::{block, Data, Json},
+    Result,
+};
+
+use db::{get_conn, models::User, PgPool};
+use errors::{Error, Validator};
+
+use super::Credentials;
+
+const MIN_PASSWORD_LENGTH: usize = 8;
+
+pub async fn register(
+    pool: Data<PgPool>,
+    identity: Identity,
+    params: Json<Credentials>,
+) -> Result<Json<User>, Error> {
+    Validator::new()
+        .required("username", &params.username, "Username is required")
+        .check(
+            params.password.chars().count() >= MIN_PASSWORD_LENGTH,
+            "password",
+            "too_short",
+            format!(
+                "Password must be at least {} characters",
+                MIN_PASSWORD_LENGTH
+            ),
+        )
+        .finish()?;
+
+    let connection = get_conn(&pool)?;
+
+    let res = block(move || User::create(&connection, &params.username, &params.password)).await?;
+    let user = res?;
+
+    // registering signs the new user in straight away
+    identity.remember(user.id.to_string());
+
+    Ok(Json(user))
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::{self, RunQueryDsl};
+
+    use db::{get_conn, models::User, new_pool, schema::users};
+    use errors::ErrorResponse;
+
+    use super::Credentials;
+    use crate::tests;
+
+    #[actix_rt::test]
+    async fn test_register() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+
+        let res: (u16, User) = tests::test_post(
+            "/api/auth/register",
+            Credentials {
+                username: "host".to_string(),
+                password: "correct horse".to_string(),
+            },
+        )
+        .await;
+
+        assert_eq!(res.0, 200);
+        assert_eq!(res.1.username, "host");
+
+        let result_users = users::dsl::users.load::<User>(&conn).unwrap();
+        assert_eq!(result_users.len(), 1);
+        assert_ne!(result_users[0].password_hash, "correct horse");
+
+        let res: (u16, ErrorResponse) = tests::test_post(
+            "/api/auth/register",
+            Credentials {
+                username: "host".to_string(),
+                password: "another password".to_string(),
+            },
+        )
+        .await;
+        assert_eq!(res.0, 409);
+
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_register_invalid_params() {
+        let res: (u16, ErrorResponse) = tests::test_post(
+            "/api/auth/register",
+            Credentials {
+                username: "".to_string(),
+                password: "correct horse".to_string(),
+            },
+        )
+        .await;
+        assert_eq!(res.0, 400);
+        assert_eq!(res.1.errors, vec!["Username is required"]);
+
+        let res: (u16, ErrorResponse) = tests::test_post(
+            "/api/auth/register",
+            Credentials {
+                username: "host".to_string(),
+                password: "short".to_string(),
+            },
+        )
+        .await;
+        assert_eq!(res.0, 400);
+        assert_eq!(res.1.errors, vec!["Password must be at least 8 characters"]);
+    }
+
+    #[actix_rt::test]
+    async fn test_register_reports_every_invalid_field() {
+        let res: (u16, ErrorResponse) = tests::test_post(
+            "/api/auth/register",
+            Credentials {
+                username: "".to_string(),
+                password: "short".to_string(),
+            },
+        )
+        .await;
+
+        assert_eq!(res.0, 400);
+        assert_eq!(
+            res.1.errors,
+            vec![
+                "Username is required",
+                "Password must be at least 8 characters"
+            ]
+        );
+
+        let fields: Vec<(Option<&str>, &str)> = res
+            .1
+            .details
+            .iter()
+            .map(|detail| (detail.field.as_deref(), detail.code.as_str()))
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                (Some("username"), "required"),
+                (Some("password"), "too_short")
+            ]
+        );
+    }
+}
@@ -0,0 +1,61 @@
+use actix_web::{
+    web::{Data, Json},
+    Result,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use errors::Error;
+
+use crate::auth::{CurrentUser, TokenKey, TOKEN_LIFETIME_SECONDS};
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct WsToken {
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// A token to open a websocket or event stream with as the signed in user, passed as
+/// `?token=`. For clients that can't send the session cookie with the handshake
+pub async fn ws_token(key: Data<TokenKey>, user: CurrentUser) -> Result<Json<WsToken>, Error> {
+    let expires_at = Utc::now() + Duration::seconds(TOKEN_LIFETIME_SECONDS);
+
+    Ok(Json(WsToken {
+        token: key.sign(user, expires_at),
+        expires_at,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::{self, RunQueryDsl};
+
+    use db::{get_conn, models::User, new_pool, schema::users};
+    use errors::ErrorResponse;
+
+    use super::WsToken;
+    use crate::auth::TokenKey;
+    use crate::tests;
+
+    #[actix_rt::test]
+    async fn test_ws_token() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+
+        let user = User::create(&conn, "token-holder", "correct horse").unwrap();
+        let cookie = tests::sign_in("token-holder", "correct horse").await;
+
+        let res: (u16, WsToken) = tests::test_post_as("/api/auth/ws-token", (), &cookie).await;
+        assert_eq!(res.0, 200);
+        let signed_for = TokenKey::new(tests::SECRET_KEY)
+            .verify(&res.1.token)
+            .unwrap();
+        assert_eq!(signed_for.id, user.id);
+        assert!(res.1.expires_at > chrono::Utc::now());
+
+        let res: (u16, ErrorResponse) = tests::test_post("/api/auth/ws-token", ()).await;
+        assert_eq!(res.0, 401);
+
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+}
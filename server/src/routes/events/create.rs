@@ -0,0 +1,124 @@
+use actix_web::{
+    web::{block, Data, Json},
+    Result,
+};
+use serde::{Deserialize, Serialize};
+
+use db::{get_conn, models::Event, PgPool};
+use errors::{Error, Validator};
+
+use crate::auth::CurrentUser;
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct CreateEventRequest {
+    name: String,
+    #[serde(default)]
+    moderated: bool,
+}
+
+pub async fn create(
+    pool: Data<PgPool>,
+    user: CurrentUser,
+    params: Json<CreateEventRequest>,
+) -> Result<Json<Event>, Error> {
+    Validator::new()
+        .required("name", &params.name, "Name is required")
+        .finish()?;
+
+    let connection = get_conn(&pool)?;
+
+    // whoever creates the event is its first host
+    let res =
+        block(move || Event::create_hosted(&connection, &params.name, params.moderated, user.id))
+            .await?;
+    let event = res?;
+
+    Ok(Json(event))
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::{self, RunQueryDsl};
+
+    use db::{
+        get_conn,
+        models::{Event, EventMember, Role, User},
+        new_pool,
+        schema::{events, users},
+    };
+    use errors::ErrorResponse;
+
+    use super::CreateEventRequest;
+    use crate::tests;
+
+    #[actix_rt::test]
+    async fn test_create_event() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+
+        let user = User::create(&conn, "organizer", "correct horse").unwrap();
+        let cookie = tests::sign_in("organizer", "correct horse").await;
+
+        let res: (u16, Event) = tests::test_post_as(
+            "/api/events",
+            CreateEventRequest {
+                name: "Town hall".to_string(),
+                moderated: false,
+            },
+            &cookie,
+        )
+        .await;
+
+        assert_eq!(res.0, 200);
+        assert_eq!(res.1.name, "Town hall");
+        assert!(!res.1.moderated);
+
+        let result_events = events::dsl::events.load::<Event>(&conn).unwrap();
+        assert_eq!(result_events.len(), 1);
+
+        let role = EventMember::role(&conn, res.1.id, user.id).unwrap();
+        assert_eq!(role, Some(Role::Host));
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_create_event_requires_sign_in() {
+        let res: (u16, ErrorResponse) = tests::test_post(
+            "/api/events",
+            CreateEventRequest {
+                name: "Town hall".to_string(),
+                moderated: false,
+            },
+        )
+        .await;
+
+        assert_eq!(res.0, 401);
+        assert_eq!(res.1.errors, vec!["Unauthorized"]);
+    }
+
+    #[actix_rt::test]
+    async fn test_create_event_name_required() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+
+        User::create(&conn, "organizer", "correct horse").unwrap();
+        let cookie = tests::sign_in("organizer", "correct horse").await;
+
+        let res: (u16, ErrorResponse) = tests::test_post_as(
+            "/api/events",
+            CreateEventRequest {
+                name: "".to_string(),
+                moderated: false,
+            },
+            &cookie,
+        )
+        .await;
+
+        assert_eq!(res.0, 400);
+        assert_eq!(res.1.errors, vec!["Name is required"]);
+
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+}
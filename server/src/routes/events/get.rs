@@ -0,0 +1,49 @@
+use actix_web::{
+    web::{block, Data, Json, Path},
+    Result,
+};
+
+use db::{get_conn, models::Event, PgPool};
+use errors::Error;
+
+pub async fn get(pool: Data<PgPool>, event_id: Path<i32>) -> Result<Json<Event>, Error> {
+    let connection = get_conn(&pool)?;
+
+    let event_id = event_id.into_inner();
+    let res = block(move || Event::find(&connection, event_id)).await?;
+    let event = res?;
+
+    Ok(Json(event))
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::{self, RunQueryDsl};
+
+    use db::{get_conn, models::Event, new_pool, schema::events};
+    use errors::ErrorResponse;
+
+    use crate::tests;
+
+    #[actix_rt::test]
+    async fn test_get_returns_event() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+
+        let event = Event::create(&conn, "an event", false).unwrap();
+
+        let res: (u16, Event) = tests::test_get(&format!("/api/events/{}", event.id)).await;
+        assert_eq!(res.0, 200);
+        assert_eq!(res.1.id, event.id);
+        assert_eq!(res.1.name, "an event");
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_get_not_found() {
+        let res: (u16, ErrorResponse) = tests::test_get("/api/events/0").await;
+        assert_eq!(res.0, 404);
+        assert_eq!(res.1.errors, vec!["Record not found"]);
+    }
+}
@@ -0,0 +1,41 @@
+use actix_web::{
+    web::{block, Data, Json},
+    Result,
+};
+
+use db::{get_conn, models::Event, PgPool};
+use errors::Error;
+
+pub async fn get_all(pool: Data<PgPool>) -> Result<Json<Vec<Event>>, Error> {
+    let connection = get_conn(&pool)?;
+
+    let res = block(move || Event::get_all(&connection)).await?;
+    let events = res?;
+
+    Ok(Json(events))
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::{self, RunQueryDsl};
+
+    use db::{get_conn, models::Event, new_pool, schema::events};
+
+    use crate::tests;
+
+    #[actix_rt::test]
+    async fn test_get_all_returns_events() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+
+        Event::create(&conn, "first event", false).unwrap();
+        Event::create(&conn, "second event", false).unwrap();
+
+        let res: (u16, Vec<Event>) = tests::test_get("/api/events").await;
+        assert_eq!(res.0, 200);
+        let names: Vec<&str> = res.1.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["first event", "second event"]);
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+    }
+}
@@ -0,0 +1,7 @@
+mod create;
+mod get;
+mod get_all;
+
+pub use self::create::*;
+pub use self::get::*;
+pub use self::get_all::*;
@@ -0,0 +1,173 @@
+use std::env;
+
+use actix_web::{
+    error::{JsonPayloadError, PathError, QueryPayloadError},
+    web::{JsonConfig, PathConfig, QueryConfig, ServiceConfig},
+    Error as ActixError, HttpRequest,
+};
+
+use errors::Error;
+
+/// Used when `JSON_BODY_LIMIT` isn't set. Questions and answers are short, so this is
+/// well below actix's own default
+const DEFAULT_JSON_BODY_LIMIT: usize = 16 * 1024;
+
+/// Requests the extractors reject never reach a handler, so they get these error handlers
+/// to send the same `ErrorResponse` as everything else instead of actix's plain text
+pub fn configure(cfg: &mut ServiceConfig) {
+    cfg.app_data(
+        JsonConfig::default()
+            .limit(json_body_limit())
+            .error_handler(json_error),
+    )
+    .app_data(PathConfig::default().error_handler(path_error))
+    .app_data(QueryConfig::default().error_handler(query_error));
+}
+
+fn json_body_limit() -> usize {
+    env::var("JSON_BODY_LIMIT")
+        .ok()
+        .and_then(|limit| limit.parse().ok())
+        .unwrap_or(DEFAULT_JSON_BODY_LIMIT)
+}
+
+fn json_error(error: JsonPayloadError, _req: &HttpRequest) -> ActixError {
+    let error = match error {
+        JsonPayloadError::OverflowKnownLength { limit, .. }
+        | JsonPayloadError::Overflow { limit } => Error::bad_request(
+            "payload_too_large",
+            format!("Request body must be at most {} bytes", limit),
+        ),
+        JsonPayloadError::ContentType => Error::bad_request(
+            "unsupported_content_type",
+            "Content-Type must be application/json",
+        ),
+        // well formed json in the wrong shape, like a missing field
+        JsonPayloadError::Deserialize(error) if error.is_data() => {
+            Error::bad_request("invalid_body", error.to_string())
+        }
+        JsonPayloadError::Deserialize(error) => {
+            Error::bad_request("invalid_json", error.to_string())
+        }
+        error => Error::bad_request("invalid_body", error.to_string()),
+    };
+
+    error.into()
+}
+
+fn path_error(error: PathError, _req: &HttpRequest) -> ActixError {
+    let message = match error {
+        PathError::Deserialize(error) => format!("Invalid path: {}", error),
+        error => error.to_string(),
+    };
+    Error::bad_request("invalid_path", message).into()
+}
+
+fn query_error(error: QueryPayloadError, _req: &HttpRequest) -> ActixError {
+    let error = match error {
+        QueryPayloadError::Deserialize(error) => match unknown_field(&error.to_string()) {
+            Some(field) => Error::invalid_field(
+                field,
+                "unknown_field",
+                format!("Unknown query parameter '{}'", field),
+            ),
+            None => Error::bad_request("invalid_query", format!("Invalid query string: {}", error)),
+        },
+        error => Error::bad_request("invalid_query", error.to_string()),
+    };
+    error.into()
+}
+
+// serde only says which field it didn't expect in its message, e.g.
+// "unknown field `sorting`, expected one of `limit`, `cursor`"
+fn unknown_field(message: &str) -> Option<&str> {
+    let rest = message.strip_prefix("unknown field `")?;
+    rest.split('`').next()
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::header::CONTENT_TYPE, test};
+
+    use errors::ErrorResponse;
+
+    use super::DEFAULT_JSON_BODY_LIMIT;
+    use crate::tests;
+
+    async fn post_raw(route: &str, content_type: &str, body: String) -> (u16, ErrorResponse) {
+        let app = tests::get_service().await;
+        let req = test::TestRequest::post()
+            .uri(route)
+            .insert_header((CONTENT_TYPE, content_type))
+            .set_payload(body);
+        let res = test::call_service(&app, req.to_request()).await;
+
+        let status = res.status().as_u16();
+        (status, test::read_body_json(res).await)
+    }
+
+    #[actix_rt::test]
+    async fn test_malformed_json() {
+        let res = post_raw(
+            "/api/events/1/questions",
+            "application/json",
+            "{\"body\":".into(),
+        )
+        .await;
+        assert_eq!(res.0, 400);
+        assert_eq!(res.1.details[0].code, "invalid_json");
+    }
+
+    #[actix_rt::test]
+    async fn test_missing_field() {
+        let res = post_raw("/api/events/1/questions", "application/json", "{}".into()).await;
+        assert_eq!(res.0, 400);
+        assert_eq!(res.1.details[0].code, "invalid_body");
+        assert!(res.1.errors[0].contains("missing field `body`"));
+    }
+
+    #[actix_rt::test]
+    async fn test_wrong_content_type() {
+        let res = post_raw(
+            "/api/events/1/questions",
+            "text/plain",
+            "{\"body\":\"a question\"}".into(),
+        )
+        .await;
+        assert_eq!(res.0, 400);
+        assert_eq!(res.1.details[0].code, "unsupported_content_type");
+    }
+
+    #[actix_rt::test]
+    async fn test_payload_too_large() {
+        let body = format!("{{\"body\":\"{}\"}}", "a".repeat(DEFAULT_JSON_BODY_LIMIT));
+        let res = post_raw("/api/events/1/questions", "application/json", body).await;
+        assert_eq!(res.0, 400);
+        assert_eq!(res.1.details[0].code, "payload_too_large");
+    }
+
+    #[actix_rt::test]
+    async fn test_invalid_path() {
+        let res: (u16, ErrorResponse) = tests::test_get("/api/events/abc").await;
+        assert_eq!(res.0, 400);
+        assert_eq!(res.1.details[0].code, "invalid_path");
+    }
+
+    #[actix_rt::test]
+    async fn test_invalid_query() {
+        let res: (u16, ErrorResponse) =
+            tests::test_get("/api/events/1/questions/search?q=hi&limit=ten").await;
+        assert_eq!(res.0, 400);
+        assert_eq!(res.1.details[0].code, "invalid_query");
+    }
+
+    #[actix_rt::test]
+    async fn test_unknown_query_param() {
+        let res: (u16, ErrorResponse) =
+            tests::test_get("/api/events/1/questions?sorting=votes").await;
+        assert_eq!(res.0, 400);
+        assert_eq!(res.1.details[0].field.as_deref(), Some("sorting"));
+        assert_eq!(res.1.details[0].code, "unknown_field");
+        assert_eq!(res.1.errors, vec!["Unknown query parameter 'sorting'"]);
+    }
+}
@@ -0,0 +1,66 @@
+use actix_web::{
+    web::{block, Data, Json, Path},
+    Result,
+};
+
+use db::{get_conn, models::EventMember, PgPool};
+use errors::Error;
+
+use crate::auth::EventHost;
+
+pub async fn get_all(
+    pool: Data<PgPool>,
+    event_id: Path<i32>,
+    _host: EventHost,
+) -> Result<Json<Vec<EventMember>>, Error> {
+    let connection = get_conn(&pool)?;
+
+    let event_id = event_id.into_inner();
+    let res = block(move || EventMember::get_all(&connection, event_id)).await?;
+    let members = res?;
+
+    Ok(Json(members))
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::{self, RunQueryDsl};
+
+    use db::{
+        get_conn,
+        models::{Event, EventMember, Role},
+        new_pool,
+        schema::{events, users},
+    };
+    use errors::ErrorResponse;
+
+    use crate::tests;
+
+    #[actix_rt::test]
+    async fn test_get_all_members() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+
+        let host = tests::sign_in_as(event.id, Role::Host).await;
+        let moderator = tests::sign_in_as(event.id, Role::Moderator).await;
+
+        let route = format!("/api/events/{}/members", event.id);
+
+        let res: (u16, Vec<EventMember>) = tests::test_get_as(&route, &host).await;
+        assert_eq!(res.0, 200);
+        assert_eq!(res.1.len(), 2);
+        assert_eq!(res.1[0].role, Role::Host);
+        assert_eq!(res.1[1].role, Role::Moderator);
+
+        let res: (u16, ErrorResponse) = tests::test_get_as(&route, &moderator).await;
+        assert_eq!(res.0, 403);
+        assert_eq!(res.1.errors, vec!["Forbidden"]);
+
+        let res: (u16, ErrorResponse) = tests::test_get(&route).await;
+        assert_eq!(res.0, 401);
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+}
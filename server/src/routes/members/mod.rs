@@ -0,0 +1,5 @@
+mod get_all;
+mod update;
+
+pub use self::get_all::*;
+pub use self::update::*;
@@ -0,0 +1,143 @@
+use actix_web::{
+    web::{block, Data, Json, Path},
+    Result,
+};
+use serde::{Deserialize, Serialize};
+
+use db::{
+    get_conn,
+    models::{EventMember, Role},
+    PgPool,
+};
+use errors::Error;
+
+use crate::auth::EventHost;
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct UpdateMemberRequest {
+    role: String,
+}
+
+pub async fn update(
+    pool: Data<PgPool>,
+    path: Path<(i32, i32)>,
+    host: EventHost,
+    params: Json<UpdateMemberRequest>,
+) -> Result<Json<EventMember>, Error> {
+    let role = Role::parse(&params.role)?;
+
+    let (event_id, user_id) = path.into_inner();
+    // keeps an event from being left without anyone who can manage it
+    if user_id == host.user.id && role != Role::Host {
+        return Err(Error::invalid_field(
+            "role",
+            "cannot_change_own_role",
+            "Hosts cannot change their own role",
+        ));
+    }
+
+    let connection = get_conn(&pool)?;
+
+    let res = block(move || EventMember::set_role(&connection, event_id, user_id, role)).await?;
+    let member = res?;
+
+    Ok(Json(member))
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::{self, RunQueryDsl};
+
+    use db::{
+        get_conn,
+        models::{Event, EventMember, Role, User},
+        new_pool,
+        schema::{events, users},
+    };
+    use errors::ErrorResponse;
+
+    use super::UpdateMemberRequest;
+    use crate::tests;
+
+    #[actix_rt::test]
+    async fn test_update_member_role() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+        let other_event = Event::create(&conn, "Another event", false).unwrap();
+
+        let host = tests::sign_in_as(event.id, Role::Host).await;
+        let user = User::create(&conn, "participant", "correct horse").unwrap();
+        let participant = tests::sign_in("participant", "correct horse").await;
+
+        let members_route = format!("/api/events/{}/members", event.id);
+
+        let res: (u16, ErrorResponse) = tests::test_get_as(&members_route, &participant).await;
+        assert_eq!(res.0, 403);
+
+        let res: (u16, EventMember) = tests::test_put_as(
+            &format!("/api/events/{}/members/{}", event.id, user.id),
+            UpdateMemberRequest {
+                role: "host".to_string(),
+            },
+            &host,
+        )
+        .await;
+        assert_eq!(res.0, 200);
+        assert_eq!(res.1.user_id, user.id);
+        assert_eq!(res.1.role, Role::Host);
+
+        let res: (u16, Vec<EventMember>) = tests::test_get_as(&members_route, &participant).await;
+        assert_eq!(res.0, 200);
+
+        // the role only applies to the event it was given in
+        let res: (u16, ErrorResponse) = tests::test_get_as(
+            &format!("/api/events/{}/members", other_event.id),
+            &participant,
+        )
+        .await;
+        assert_eq!(res.0, 403);
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_update_member_invalid_params() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+
+        let host = tests::sign_in_as(event.id, Role::Host).await;
+        let members = EventMember::get_all(&conn, event.id).unwrap();
+        let route = format!("/api/events/{}/members/{}", event.id, members[0].user_id);
+
+        let res: (u16, ErrorResponse) = tests::test_put_as(
+            &route,
+            UpdateMemberRequest {
+                role: "owner".to_string(),
+            },
+            &host,
+        )
+        .await;
+        assert_eq!(res.0, 400);
+        assert_eq!(
+            res.1.errors,
+            vec!["Unknown role 'owner', expected one of: participant, moderator, host"]
+        );
+
+        let res: (u16, ErrorResponse) = tests::test_put_as(
+            &route,
+            UpdateMemberRequest {
+                role: "participant".to_string(),
+            },
+            &host,
+        )
+        .await;
+        assert_eq!(res.0, 400);
+        assert_eq!(res.1.errors, vec!["Hosts cannot change their own role"]);
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+}
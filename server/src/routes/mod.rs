@@ -2,15 +2,54 @@ use actix_web::web;
 
 use crate::websocket;
 
+pub mod answers;
+pub mod auth;
+pub mod events;
+mod extractors;
+pub mod members;
+pub mod presence;
 pub mod questions;
+pub mod sessions;
+pub mod votes;
 
+// laid out by hand so the nesting follows the paths
+#[rustfmt::skip]
 pub fn routes(cfg: &mut web::ServiceConfig) {
+    extractors::configure(cfg);
+
     cfg.service(
         web::resource("/ws/").route(web::get().to(websocket::ws_index))
     ).service(
         web::scope("/api")
-            .service(web::scope("/questions")
-                .route("", web::get().to(questions::get_all))
-                .route("", web::post().to(questions::create))),
+            .service(web::scope("/auth")
+                .route("/register", web::post().to(auth::register))
+                .route("/login", web::post().to(auth::login))
+                .route("/logout", web::post().to(auth::logout))
+                .route("/me", web::get().to(auth::me))
+                .route("/ws-token", web::post().to(auth::ws_token)))
+            .route("/presence", web::get().to(presence::get))
+            .service(web::scope("/events")
+                .route("", web::get().to(events::get_all))
+                .route("", web::post().to(events::create))
+                .route("/stream", web::get().to(websocket::event_stream))
+                .route("/{event_id}", web::get().to(events::get))
+                .route("/{event_id}/members", web::get().to(members::get_all))
+                .route("/{event_id}/members/{user_id}", web::put().to(members::update))
+                .route("/{event_id}/sessions", web::get().to(sessions::get_all))
+                .service(web::scope("/{event_id}/questions")
+                    .route("", web::get().to(questions::get_all))
+                    .route("", web::post().to(questions::create))
+                    .route("/search", web::get().to(questions::search))
+                    .route("/{id}", web::get().to(questions::get))
+                    .route("/{id}", web::patch().to(questions::update))
+                    .route("/{id}", web::delete().to(questions::delete))
+                    .route("/{id}/restore", web::post().to(questions::restore))
+                    .route("/{id}/approve", web::post().to(questions::approve))
+                    .route("/{id}/reject", web::post().to(questions::reject))
+                    .route("/{id}/status", web::post().to(questions::update_status))
+                    .route("/{id}/answers", web::get().to(answers::get_all))
+                    .route("/{id}/answers", web::post().to(answers::create))
+                    .route("/{id}/vote", web::post().to(votes::create))
+                    .route("/{id}/vote", web::delete().to(votes::delete)))),
     );
 }
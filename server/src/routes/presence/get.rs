@@ -0,0 +1,111 @@
+use actix::Addr;
+use actix_web::{
+    web::{block, Data, Json, Query},
+    Result,
+};
+use serde::{Deserialize, Serialize};
+
+use db::{get_conn, models::Event, PgPool};
+use errors::Error;
+
+use crate::websocket::{GetPresence, Presence, Server};
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct PresenceParams {
+    event_id: Option<i32>,
+}
+
+pub async fn get(
+    pool: Data<PgPool>,
+    websocket_srv: Data<Addr<Server>>,
+    params: Query<PresenceParams>,
+) -> Result<Json<Presence>, Error> {
+    let event_id = params
+        .event_id
+        .ok_or_else(|| Error::invalid_field("event_id", "required", "event_id is required"))?;
+
+    let connection = get_conn(&pool)?;
+    let res = block(move || Event::find(&connection, event_id)).await?;
+    res?;
+
+    let presence = websocket_srv
+        .send(GetPresence { event_id })
+        .await
+        .map_err(|err| Error::InternalServerError(err.to_string()))?;
+
+    Ok(Json(presence))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web_actors::ws;
+    use awc::Client;
+    use diesel::{self, RunQueryDsl};
+    use futures::{SinkExt, StreamExt};
+
+    use db::{get_conn, models::Event, new_pool, schema::events};
+    use errors::ErrorResponse;
+
+    use crate::tests;
+    use crate::websocket::{Presence, ServerEvent};
+
+    #[actix_rt::test]
+    async fn test_presence() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+
+        let srv = tests::get_test_server();
+        let url = tests::websocket_url(&srv, event.id);
+
+        let client = Client::default();
+        let (_, mut first) = client.ws(&url).connect().await.unwrap();
+        let (_, second) = client.ws(&url).connect().await.unwrap();
+
+        // presence is debounced, so the first client may or may not see a count of 1 first
+        let mut participants = 0;
+        while participants != 2 {
+            let frame = first.next().await.unwrap().unwrap();
+            match tests::get_websocket_frame_data(frame).unwrap().event {
+                ServerEvent::Presence(presence) => participants = presence.participants,
+                event => panic!("expected presence, got {:?}", event),
+            }
+        }
+
+        let mut res = srv
+            .get(format!("/api/presence?event_id={}", event.id))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        let presence: Presence = res.json().await.unwrap();
+        assert_eq!(presence.event_id, event.id);
+        assert_eq!(presence.participants, 2);
+
+        // leaving without a close frame counts too
+        drop(second);
+
+        let frame = first.next().await.unwrap().unwrap();
+        match tests::get_websocket_frame_data(frame).unwrap().event {
+            ServerEvent::Presence(presence) => assert_eq!(presence.participants, 1),
+            event => panic!("expected presence, got {:?}", event),
+        }
+
+        first.send(ws::Message::Close(None)).await.unwrap();
+        drop(first);
+
+        srv.stop().await;
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_presence_requires_an_event() {
+        let res: (u16, ErrorResponse) = tests::test_get("/api/presence").await;
+        assert_eq!(res.0, 400);
+        assert_eq!(res.1.errors, vec!["event_id is required"]);
+
+        let res: (u16, ErrorResponse) = tests::test_get("/api/presence?event_id=0").await;
+        assert_eq!(res.0, 404);
+    }
+}
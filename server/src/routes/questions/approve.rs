@@ -0,0 +1,150 @@
+use actix::Addr;
+use actix_web::{
+    web::{block, Data, Json, Path},
+    Result,
+};
+
+use db::{get_conn, models::Question, PgPool};
+use errors::Error;
+
+use crate::auth::EventModerator;
+use crate::websocket::{MessageToClient, Server, ServerEvent};
+
+pub async fn approve(
+    pool: Data<PgPool>,
+    websocket_srv: Data<Addr<Server>>,
+    path: Path<(i32, i32)>,
+    _moderator: EventModerator,
+) -> Result<Json<Question>, Error> {
+    let connection = get_conn(&pool)?;
+
+    let (event_id, question_id) = path.into_inner();
+    let res = block(move || Question::approve(&connection, event_id, question_id)).await?;
+    let question = res?;
+
+    // participants never saw the pending question, so it is announced as new
+    let msg = MessageToClient::new(event_id, ServerEvent::NewQuestion(question.clone()));
+    websocket_srv.do_send(msg);
+
+    Ok(Json(question))
+}
+
+#[cfg(test)]
+mod tests {
+    use awc::Client;
+    use diesel::{self, RunQueryDsl};
+
+    use db::{
+        get_conn,
+        models::{Event, NewQuestion, Question, QuestionPage, QuestionStatus, Role},
+        new_pool,
+        schema::{events, questions, users},
+    };
+    use errors::ErrorResponse;
+
+    use crate::tests;
+    use crate::websocket::ServerEvent;
+
+    #[actix_rt::test]
+    pub async fn test_approve_question() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", true).unwrap();
+
+        let moderator = tests::sign_in_as(event.id, Role::Moderator).await;
+
+        let question = diesel::insert_into(questions::table)
+            .values(NewQuestion {
+                body: "A pending question".to_string(),
+                event_id: event.id,
+                status: QuestionStatus::Pending,
+                author_id: None,
+            })
+            .get_result::<Question>(&conn)
+            .unwrap();
+
+        let res: (u16, QuestionPage) =
+            tests::test_get(&format!("/api/events/{}/questions", event.id)).await;
+        assert_eq!(res.0, 200);
+        assert_eq!(res.1.questions.len(), 0);
+
+        let srv = tests::get_test_server();
+
+        let client = Client::default();
+        let ws_conn = client
+            .ws(tests::websocket_url(&srv, event.id))
+            .connect()
+            .await
+            .unwrap();
+
+        let mut res = srv
+            .post(format!(
+                "/api/events/{}/questions/{}/approve",
+                event.id, question.id
+            ))
+            .cookie(moderator.clone())
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(res.status().as_u16(), 200);
+
+        let approved: Question = res.json().await.unwrap();
+        assert_eq!(approved.status, QuestionStatus::Open);
+
+        let mut stream = ws_conn.1;
+        let frame = tests::next_websocket_frame(&mut stream).await;
+
+        let data = tests::get_websocket_frame_data(frame);
+        let msg = data.expect("Message was not a string");
+        let question = match msg.event {
+            ServerEvent::NewQuestion(question) => question,
+            event => panic!("expected newquestion, got {:?}", event),
+        };
+        assert_eq!(question.id, approved.id);
+
+        drop(stream);
+
+        srv.stop().await;
+
+        let res: (u16, QuestionPage) =
+            tests::test_get(&format!("/api/events/{}/questions", event.id)).await;
+        assert_eq!(res.0, 200);
+        assert_eq!(res.1.questions.len(), 1);
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    pub async fn test_approve_open_question() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", true).unwrap();
+
+        let moderator = tests::sign_in_as(event.id, Role::Moderator).await;
+
+        let question = diesel::insert_into(questions::table)
+            .values(NewQuestion {
+                body: "An open question".to_string(),
+                event_id: event.id,
+                status: QuestionStatus::Open,
+                author_id: None,
+            })
+            .get_result::<Question>(&conn)
+            .unwrap();
+
+        let res: (u16, ErrorResponse) = tests::test_post_as(
+            &format!("/api/events/{}/questions/{}/approve", event.id, question.id),
+            (),
+            &moderator,
+        )
+        .await;
+
+        assert_eq!(res.0, 400);
+        assert_eq!(res.1.errors, vec!["Question is open, not pending"]);
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+}
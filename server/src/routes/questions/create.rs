@@ -1,15 +1,15 @@
 use actix::Addr;
 use actix_web::{
-    web::{block, Data, Json},
+    web::{block, Data, Json, Path},
     Result,
 };
 use serde::{Deserialize, Serialize};
-use serde_json::to_value;
 
 use db::{get_conn, models::Question, PgPool};
-use errors::Error;
+use errors::{Error, Validator};
 
-use crate::websocket::{MessageToClient, Server};
+use crate::auth::CurrentUser;
+use crate::websocket::{MessageToClient, Server, ServerEvent};
 
 #[derive(Clone, Deserialize, Serialize)]
 pub struct CreateRequest {
@@ -19,21 +19,25 @@ pub struct CreateRequest {
 pub async fn create(
     pool: Data<PgPool>,
     websocket_srv: Data<Addr<Server>>,
+    event_id: Path<i32>,
+    user: Option<CurrentUser>,
     params: Json<CreateRequest>,
 ) -> Result<Json<Question>, Error> {
-    if params.body == "" {
-        return Err(Error::BadRequest("Body is required".to_string()));
-    }
+    Validator::new()
+        .required("body", &params.body, "Body is required")
+        .finish()?;
 
     let connection = get_conn(&pool)?;
 
-    let res = block(move || Question::create(&connection, &params.body)).await?;
+    let event_id = event_id.into_inner();
+    let author_id = user.map(|user| user.id);
+    let res =
+        block(move || Question::create(&connection, event_id, author_id, &params.body)).await?;
     let question = res?;
 
-    if let Ok(question) = to_value(question.clone()) {
-        let msg = MessageToClient::new("newquestion", question);
-        websocket_srv.do_send(msg);
-    }
+    // pending questions only go out to moderators, see `ServerEvent::moderators_only`
+    let msg = MessageToClient::new(event_id, ServerEvent::NewQuestion(question.clone()));
+    websocket_srv.do_send(msg);
 
     Ok(Json(question))
 }
@@ -42,33 +46,41 @@ pub async fn create(
 mod tests {
     use awc::Client;
     use diesel::{self, RunQueryDsl};
-    use futures::StreamExt;
-    use serde_json;
 
     use db::{
         get_conn,
-        models::{NewQuestion, Question},
+        models::{Event, NewQuestion, Question, QuestionPage, QuestionStatus, Role, User},
         new_pool,
-        schema::questions,
+        schema::{events, questions, users},
     };
     use errors::ErrorResponse;
 
+    use super::CreateRequest;
     use crate::tests;
+    use crate::websocket::ServerEvent;
 
     #[actix_rt::test]
     pub async fn test_create_question() {
         let pool = new_pool();
         let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
 
         let srv = tests::get_test_server();
 
         let client = Client::default();
-        let ws_conn = client.ws(srv.url("/ws/")).connect().await.unwrap();
+        let ws_conn = client
+            .ws(tests::websocket_url(&srv, event.id))
+            .connect()
+            .await
+            .unwrap();
 
         let mut res = srv
-            .post("/api/questions")
+            .post(&format!("/api/events/{}/questions", event.id))
             .send_json(&NewQuestion {
                 body: "A new question".to_string(),
+                event_id: event.id,
+                status: QuestionStatus::Open,
+                author_id: None,
             })
             .await
             .unwrap();
@@ -78,14 +90,16 @@ mod tests {
         let question: Question = res.json().await.unwrap();
         assert_eq!(question.body, "A new question");
 
-        let mut stream = ws_conn.1.take(1);
-        let msg = stream.next().await;
+        let mut stream = ws_conn.1;
+        let frame = tests::next_websocket_frame(&mut stream).await;
 
-        let data = tests::get_websocket_frame_data(msg.unwrap().unwrap());
+        let data = tests::get_websocket_frame_data(frame);
         if data.is_some() {
             let msg = data.unwrap();
-            assert_eq!(msg.msg_type, "newquestion");
-            let question: Question = serde_json::from_value(msg.data).unwrap();
+            let question = match msg.event {
+                ServerEvent::NewQuestion(question) => question,
+                event => panic!("expected newquestion, got {:?}", event),
+            };
             assert_eq!(question.body, "A new question");
         } else {
             assert!(false, "Message was not a string");
@@ -99,20 +113,22 @@ mod tests {
 
         srv.stop().await;
 
-        diesel::delete(questions::dsl::questions)
-            .execute(&conn)
-            .unwrap();
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
     }
 
     #[actix_rt::test]
     pub async fn test_create_body_required() {
         let pool = new_pool();
         let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
 
         let res: (u16, ErrorResponse) = tests::test_post(
-            "/api/questions",
+            &format!("/api/events/{}/questions", event.id),
             NewQuestion {
                 body: "".to_string(),
+                event_id: event.id,
+                status: QuestionStatus::Open,
+                author_id: None,
             },
         )
         .await;
@@ -122,5 +138,167 @@ mod tests {
 
         let result_questions = questions::dsl::questions.load::<Question>(&conn).unwrap();
         assert_eq!(result_questions.len(), 0);
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    pub async fn test_create_question_in_moderated_event() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "A moderated event", true).unwrap();
+
+        let moderator = tests::sign_in_as(event.id, Role::Moderator).await;
+
+        let srv = tests::get_test_server();
+
+        let client = Client::default();
+        let ws_conn = client
+            .ws(tests::websocket_url(&srv, event.id))
+            .connect()
+            .await
+            .unwrap();
+        // moderators can use their session cookie
+        let (_, mut moderator_conn) = client
+            .ws(srv.url(&format!("/ws/?event_id={}", event.id)))
+            .cookie(moderator.clone())
+            .connect()
+            .await
+            .unwrap();
+
+        let mut res = srv
+            .post(format!("/api/events/{}/questions", event.id))
+            .send_json(&NewQuestion {
+                body: "A question to moderate".to_string(),
+                event_id: event.id,
+                status: QuestionStatus::Open,
+                author_id: None,
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(res.status().as_u16(), 200);
+
+        let question: Question = res.json().await.unwrap();
+        assert_eq!(question.status, QuestionStatus::Pending);
+
+        let frame = tests::next_websocket_frame(&mut moderator_conn).await;
+        let msg = tests::get_websocket_frame_data(frame).unwrap();
+        match msg.event {
+            ServerEvent::NewQuestion(pending) => {
+                assert_eq!(pending.status, QuestionStatus::Pending)
+            }
+            event => panic!("expected newquestion, got {:?}", event),
+        }
+
+        // participants only hear about the approval, so their first frame is the newquestion
+        // it sends
+        let res = srv
+            .post(format!(
+                "/api/events/{}/questions/{}/approve",
+                event.id, question.id
+            ))
+            .cookie(moderator)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+
+        let mut stream = ws_conn.1;
+        let frame = tests::next_websocket_frame(&mut stream).await;
+
+        let data = tests::get_websocket_frame_data(frame);
+        let msg = data.expect("Message was not a string");
+        let broadcast = match msg.event {
+            ServerEvent::NewQuestion(broadcast) => broadcast,
+            event => panic!("expected newquestion, got {:?}", event),
+        };
+        assert_eq!(broadcast.id, question.id);
+        assert_eq!(broadcast.status, QuestionStatus::Open);
+
+        drop(stream);
+        drop(moderator_conn);
+
+        srv.stop().await;
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    pub async fn test_pending_questions_are_listed_by_status() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "A moderated event", true).unwrap();
+
+        let res: (u16, Question) = tests::test_post(
+            &format!("/api/events/{}/questions", event.id),
+            NewQuestion {
+                body: "A question to moderate".to_string(),
+                event_id: event.id,
+                status: QuestionStatus::Open,
+                author_id: None,
+            },
+        )
+        .await;
+        assert_eq!(res.0, 200);
+        assert_eq!(res.1.status, QuestionStatus::Pending);
+
+        let res: (u16, QuestionPage) =
+            tests::test_get(&format!("/api/events/{}/questions", event.id)).await;
+        assert_eq!(res.0, 200);
+        assert_eq!(res.1.questions.len(), 0);
+
+        // the moderation queue is only listed for moderators
+        let route = format!("/api/events/{}/questions?status=pending", event.id);
+        let res: (u16, ErrorResponse) = tests::test_get(&route).await;
+        assert_eq!(res.0, 401);
+
+        let participant = tests::sign_in_as(event.id, Role::Participant).await;
+        let res: (u16, ErrorResponse) = tests::test_get_as(&route, &participant).await;
+        assert_eq!(res.0, 403);
+
+        let moderator = tests::sign_in_as(event.id, Role::Moderator).await;
+        let res: (u16, QuestionPage) = tests::test_get_as(&route, &moderator).await;
+        assert_eq!(res.0, 200);
+        assert_eq!(res.1.questions.len(), 1);
+        assert_eq!(res.1.questions[0].body, "A question to moderate");
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    pub async fn test_create_question_records_author() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+        let user = User::create(&conn, "asker", "correct horse").unwrap();
+
+        let cookie = tests::sign_in("asker", "correct horse").await;
+
+        let res: (u16, Question) = tests::test_post_as(
+            &format!("/api/events/{}/questions", event.id),
+            CreateRequest {
+                body: "A question with an author".to_string(),
+            },
+            &cookie,
+        )
+        .await;
+        assert_eq!(res.0, 200);
+        assert_eq!(res.1.author_id, Some(user.id));
+
+        let res: (u16, Question) = tests::test_post(
+            &format!("/api/events/{}/questions", event.id),
+            CreateRequest {
+                body: "An anonymous question".to_string(),
+            },
+        )
+        .await;
+        assert_eq!(res.0, 200);
+        assert_eq!(res.1.author_id, None);
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
     }
 }
@@ -0,0 +1,160 @@
+use actix::Addr;
+use actix_web::{
+    web::{block, Data, Json, Path},
+    Result,
+};
+
+use db::{get_conn, models::Question, PgPool};
+use errors::Error;
+
+use crate::auth::{require_author_or_moderator, CurrentUser};
+use crate::websocket::{MessageToClient, Server, ServerEvent};
+
+/// Soft deletes a question, for its author or a moderator
+pub async fn delete(
+    pool: Data<PgPool>,
+    websocket_srv: Data<Addr<Server>>,
+    path: Path<(i32, i32)>,
+    user: CurrentUser,
+) -> Result<Json<Question>, Error> {
+    let connection = get_conn(&pool)?;
+
+    let (event_id, question_id) = path.into_inner();
+    let res = block(move || {
+        let question = Question::find(&connection, event_id, question_id)?;
+        require_author_or_moderator(&connection, user, &question)?;
+        Question::delete(&connection, event_id, question_id)
+    })
+    .await?;
+    let question = res?;
+
+    let msg = MessageToClient::new(event_id, ServerEvent::DeletedQuestion(question.clone()));
+    websocket_srv.do_send(msg);
+
+    Ok(Json(question))
+}
+
+#[cfg(test)]
+mod tests {
+    use awc::Client;
+    use diesel::{self, RunQueryDsl};
+
+    use db::{
+        get_conn,
+        models::{Event, NewQuestion, Question, QuestionPage, QuestionStatus, Role, User},
+        new_pool,
+        schema::{events, questions, users},
+    };
+    use errors::ErrorResponse;
+
+    use crate::tests;
+    use crate::websocket::ServerEvent;
+
+    #[actix_rt::test]
+    pub async fn test_delete_question() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+
+        let moderator = tests::sign_in_as(event.id, Role::Moderator).await;
+
+        let question = diesel::insert_into(questions::table)
+            .values(NewQuestion {
+                body: "A question to delete".to_string(),
+                event_id: event.id,
+                status: QuestionStatus::Open,
+                author_id: None,
+            })
+            .get_result::<Question>(&conn)
+            .unwrap();
+
+        let srv = tests::get_test_server();
+
+        let client = Client::default();
+        let ws_conn = client
+            .ws(tests::websocket_url(&srv, event.id))
+            .connect()
+            .await
+            .unwrap();
+
+        let mut res = srv
+            .delete(format!(
+                "/api/events/{}/questions/{}",
+                event.id, question.id
+            ))
+            .cookie(moderator.clone())
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(res.status().as_u16(), 200);
+
+        let deleted: Question = res.json().await.unwrap();
+        assert_eq!(deleted.id, question.id);
+        assert!(deleted.deleted_at.is_some());
+
+        let mut stream = ws_conn.1;
+        let frame = tests::next_websocket_frame(&mut stream).await;
+
+        let data = tests::get_websocket_frame_data(frame);
+        let msg = data.expect("Message was not a string");
+        let question = match msg.event {
+            ServerEvent::DeletedQuestion(question) => question,
+            event => panic!("expected deletedquestion, got {:?}", event),
+        };
+        assert_eq!(question.id, deleted.id);
+
+        drop(stream);
+
+        let res = srv
+            .delete(format!(
+                "/api/events/{}/questions/{}",
+                event.id, question.id
+            ))
+            .cookie(moderator.clone())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 404);
+
+        srv.stop().await;
+
+        let res: (u16, QuestionPage) =
+            tests::test_get(&format!("/api/events/{}/questions", event.id)).await;
+        assert_eq!(res.0, 200);
+        assert_eq!(res.1.questions.len(), 0);
+
+        let result_questions = questions::dsl::questions.load::<Question>(&conn).unwrap();
+        assert_eq!(result_questions.len(), 1);
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    pub async fn test_delete_own_question() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+
+        let author = User::create(&conn, "delete-author", "correct horse").unwrap();
+        let question =
+            Question::create(&conn, event.id, Some(author.id), &"Oops".to_string()).unwrap();
+        let route = format!("/api/events/{}/questions/{}", event.id, question.id);
+
+        let participant = tests::sign_in_as(event.id, Role::Participant).await;
+        let res: (u16, ErrorResponse) = tests::test_delete_as(&route, &participant).await;
+        assert_eq!(res.0, 403);
+        assert_eq!(res.1.errors, vec!["Forbidden"]);
+        assert!(Question::find(&conn, event.id, question.id).is_ok());
+
+        let cookie = tests::sign_in("delete-author", "correct horse").await;
+        let res: (u16, Question) = tests::test_delete_as(&route, &cookie).await;
+        assert_eq!(res.0, 200);
+        assert!(res.1.deleted_at.is_some());
+        assert!(Question::find(&conn, event.id, question.id).is_err());
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+}
@@ -0,0 +1,104 @@
+use actix_web::{
+    web::{block, Data, Json, Path},
+    Result,
+};
+
+use db::{get_conn, models::Question, PgPool};
+use errors::Error;
+
+use crate::auth::EventModerator;
+
+/// Pending, rejected and archived questions are only found for moderators
+pub async fn get(
+    pool: Data<PgPool>,
+    path: Path<(i32, i32)>,
+    moderator: Option<EventModerator>,
+) -> Result<Json<Question>, Error> {
+    let connection = get_conn(&pool)?;
+
+    let find = match moderator {
+        Some(_) => Question::find,
+        None => Question::find_visible,
+    };
+    let (event_id, question_id) = path.into_inner();
+    let res = block(move || find(&connection, event_id, question_id)).await?;
+    let question = res?;
+
+    Ok(Json(question))
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::{self, RunQueryDsl};
+
+    use db::{
+        get_conn,
+        models::{Event, NewQuestion, Question, QuestionStatus, Role},
+        new_pool,
+        schema::{events, questions, users},
+    };
+    use errors::ErrorResponse;
+
+    use crate::tests;
+
+    #[actix_rt::test]
+    async fn test_get_returns_question() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+
+        let question = diesel::insert_into(questions::table)
+            .values(NewQuestion {
+                body: "one question".to_string(),
+                event_id: event.id,
+                status: QuestionStatus::Open,
+                author_id: None,
+            })
+            .get_result::<Question>(&conn)
+            .unwrap();
+
+        let res: (u16, Question) = tests::test_get(&format!(
+            "/api/events/{}/questions/{}",
+            event.id, question.id
+        ))
+        .await;
+        assert_eq!(res.0, 200);
+        assert_eq!(res.1.id, question.id);
+        assert_eq!(res.1.body, "one question");
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_get_hides_pending_questions() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "A moderated event", true).unwrap();
+
+        let question = Question::create(&conn, event.id, None, &"Not yet".to_string()).unwrap();
+        let route = format!("/api/events/{}/questions/{}", event.id, question.id);
+
+        let res: (u16, ErrorResponse) = tests::test_get(&route).await;
+        assert_eq!(res.0, 404);
+        assert_eq!(res.1.errors, vec!["Record not found"]);
+
+        let participant = tests::sign_in_as(event.id, Role::Participant).await;
+        let res: (u16, ErrorResponse) = tests::test_get_as(&route, &participant).await;
+        assert_eq!(res.0, 404);
+
+        let moderator = tests::sign_in_as(event.id, Role::Moderator).await;
+        let res: (u16, Question) = tests::test_get_as(&route, &moderator).await;
+        assert_eq!(res.0, 200);
+        assert_eq!(res.1.body, "Not yet");
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_get_not_found() {
+        let res: (u16, ErrorResponse) = tests::test_get("/api/events/0/questions/0").await;
+        assert_eq!(res.0, 404);
+        assert_eq!(res.1.errors, vec!["Record not found"]);
+    }
+}
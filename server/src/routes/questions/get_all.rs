@@ -1,30 +1,54 @@
 use actix_web::{
-    web::{block, Data, Json},
+    web::{block, Data, Json, Path, Query},
     Result,
 };
 
-use db::{get_conn, models::Question, PgPool};
+use db::{
+    get_conn,
+    models::{Question, QuestionFilters, QuestionPage, QuestionStatus},
+    PgPool,
+};
 use errors::Error;
 
-pub async fn get_all(pool: Data<PgPool>) -> Result<Json<Vec<Question>>, Error> {
+use crate::auth::EventModerator;
+
+/// Anyone can list the visible statuses, which are listed by default. Asking for any other
+/// status, like the moderation queue, needs a moderator
+pub async fn get_all(
+    pool: Data<PgPool>,
+    event_id: Path<i32>,
+    params: Query<QuestionFilters>,
+    moderator: Result<EventModerator, Error>,
+) -> Result<Json<QuestionPage>, Error> {
+    // an unknown status is left for `Question::get_all` to report
+    let status = params.status.as_deref().map(QuestionStatus::parse);
+    if let Some(Ok(status)) = status {
+        if !status.is_visible() {
+            moderator?;
+        }
+    }
+
     let connection = get_conn(&pool)?;
 
-    let res = block(move || Question::get_all(&connection)).await?;
-    let questions = res?;
+    let event_id = event_id.into_inner();
+    let res = block(move || Question::get_all(&connection, event_id, &params)).await?;
+    let page = res?;
 
-    Ok(Json(questions))
+    Ok(Json(page))
 }
 
 #[cfg(test)]
 mod tests {
-    use diesel::RunQueryDsl;
+    use chrono::{Duration, Utc};
+    use diesel::{self, ExpressionMethods, QueryDsl, RunQueryDsl};
 
     use db::{
         get_conn,
-        models::{NewQuestion, Question},
+        models::{Event, NewQuestion, Question, QuestionPage, QuestionStatus, User, Vote},
         new_pool,
-        schema::questions,
+        schema::{events, questions, users},
     };
+    use errors::ErrorResponse;
 
     use crate::tests;
 
@@ -32,17 +56,270 @@ mod tests {
     async fn test_get_all_returns_questions() {
         let pool = new_pool();
         let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
 
         diesel::insert_into(questions::table)
             .values(NewQuestion {
                 body: "one question".to_string(),
+                event_id: event.id,
+                status: QuestionStatus::Open,
+                author_id: None,
+            })
+            .execute(&conn)
+            .unwrap();
+
+        let res: (u16, QuestionPage) =
+            tests::test_get(&format!("/api/events/{}/questions", event.id)).await;
+        assert_eq!(res.0, 200);
+        assert_eq!(res.1.questions.len(), 1);
+        assert_eq!(res.1.questions[0].body, "one question");
+        assert_eq!(res.1.next_cursor, None);
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_get_all_paginates_with_cursor() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+
+        for body in &["a", "b", "c"] {
+            diesel::insert_into(questions::table)
+                .values(NewQuestion {
+                    body: body.to_string(),
+                    event_id: event.id,
+                    status: QuestionStatus::Open,
+                    author_id: None,
+                })
+                .execute(&conn)
+                .unwrap();
+        }
+
+        let res: (u16, QuestionPage) =
+            tests::test_get(&format!("/api/events/{}/questions?limit=2", event.id)).await;
+        assert_eq!(res.0, 200);
+        let bodies: Vec<&str> = res.1.questions.iter().map(|q| q.body.as_str()).collect();
+        assert_eq!(bodies, vec!["a", "b"]);
+        let cursor = res.1.next_cursor.expect("Expected a next_cursor");
+
+        // a question inserted before the cursor should not shift the next page
+        diesel::insert_into(questions::table)
+            .values(NewQuestion {
+                body: "aa".to_string(),
+                event_id: event.id,
+                status: QuestionStatus::Open,
+                author_id: None,
             })
             .execute(&conn)
             .unwrap();
 
-        let res: (u16, Vec<Question>) = tests::test_get("/api/questions").await;
+        let res: (u16, QuestionPage) = tests::test_get(&format!(
+            "/api/events/{}/questions?limit=2&cursor={}",
+            event.id, cursor
+        ))
+        .await;
         assert_eq!(res.0, 200);
-        assert_eq!(res.1.len(), 1);
-        assert_eq!(res.1[0].body, "one question");
+        let bodies: Vec<&str> = res.1.questions.iter().map(|q| q.body.as_str()).collect();
+        assert_eq!(bodies, vec!["c"]);
+        assert_eq!(res.1.next_cursor, None);
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_get_all_invalid_params() {
+        let res: (u16, ErrorResponse) =
+            tests::test_get("/api/events/0/questions?cursor=nope").await;
+        assert_eq!(res.0, 400);
+        assert_eq!(res.1.errors, vec!["Invalid cursor"]);
+
+        let res: (u16, ErrorResponse) = tests::test_get("/api/events/0/questions?limit=0").await;
+        assert_eq!(res.0, 400);
+        assert_eq!(res.1.errors, vec!["limit must be between 1 and 100"]);
+    }
+
+    #[actix_rt::test]
+    async fn test_get_all_sorts_and_filters_by_created_at() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+
+        let now = Utc::now();
+        for (body, age) in &[("oldest", 3), ("middle", 2), ("newest", 1)] {
+            let question = diesel::insert_into(questions::table)
+                .values(NewQuestion {
+                    body: body.to_string(),
+                    event_id: event.id,
+                    status: QuestionStatus::Open,
+                    author_id: None,
+                })
+                .get_result::<Question>(&conn)
+                .unwrap();
+
+            diesel::update(questions::dsl::questions.find(question.id))
+                .set(questions::dsl::created_at.eq(now - Duration::hours(*age)))
+                .execute(&conn)
+                .unwrap();
+        }
+
+        let res: (u16, QuestionPage) = tests::test_get(&format!(
+            "/api/events/{}/questions?sort=created_at&direction=desc&limit=2",
+            event.id
+        ))
+        .await;
+        assert_eq!(res.0, 200);
+        let bodies: Vec<&str> = res.1.questions.iter().map(|q| q.body.as_str()).collect();
+        assert_eq!(bodies, vec!["newest", "middle"]);
+        let cursor = res.1.next_cursor.expect("Expected a next_cursor");
+
+        let res: (u16, QuestionPage) = tests::test_get(&format!(
+            "/api/events/{}/questions?sort=created_at&direction=desc&limit=2&cursor={}",
+            event.id, cursor
+        ))
+        .await;
+        assert_eq!(res.0, 200);
+        let bodies: Vec<&str> = res.1.questions.iter().map(|q| q.body.as_str()).collect();
+        assert_eq!(bodies, vec!["oldest"]);
+
+        // the cursor was issued for a different ordering
+        let res: (u16, ErrorResponse) = tests::test_get(&format!(
+            "/api/events/{}/questions?cursor={}",
+            event.id, cursor
+        ))
+        .await;
+        assert_eq!(res.0, 400);
+        assert_eq!(
+            res.1.errors,
+            vec!["Cursor does not match the requested sort"]
+        );
+
+        let after = (now - Duration::minutes(150))
+            .to_rfc3339()
+            .replace("+", "%2B");
+        let before = (now - Duration::minutes(30))
+            .to_rfc3339()
+            .replace("+", "%2B");
+        let res: (u16, QuestionPage) = tests::test_get(&format!(
+            "/api/events/{}/questions?sort=created_at&created_after={}&created_before={}",
+            event.id, after, before
+        ))
+        .await;
+        assert_eq!(res.0, 200);
+        let bodies: Vec<&str> = res.1.questions.iter().map(|q| q.body.as_str()).collect();
+        assert_eq!(bodies, vec!["middle", "newest"]);
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_get_all_sorts_by_vote_count() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+        let voters: Vec<User> = (0..2)
+            .map(|voter| User::create(&conn, &format!("voter-{}", voter), "correct horse").unwrap())
+            .collect();
+
+        for (body, vote_count) in &[("one vote", 1), ("no votes", 0), ("two votes", 2)] {
+            let question = diesel::insert_into(questions::table)
+                .values(NewQuestion {
+                    body: body.to_string(),
+                    event_id: event.id,
+                    status: QuestionStatus::Open,
+                    author_id: None,
+                })
+                .get_result::<Question>(&conn)
+                .unwrap();
+
+            for voter in &voters[..*vote_count] {
+                Vote::create(&conn, event.id, question.id, voter.id).unwrap();
+            }
+        }
+
+        let res: (u16, QuestionPage) = tests::test_get(&format!(
+            "/api/events/{}/questions?sort=vote_count&direction=desc&limit=2",
+            event.id
+        ))
+        .await;
+        assert_eq!(res.0, 200);
+        let counts: Vec<(&str, i32)> = res
+            .1
+            .questions
+            .iter()
+            .map(|q| (q.body.as_str(), q.vote_count))
+            .collect();
+        assert_eq!(counts, vec![("two votes", 2), ("one vote", 1)]);
+
+        let res: (u16, QuestionPage) = tests::test_get(&format!(
+            "/api/events/{}/questions?sort=vote_count&direction=desc&limit=2&cursor={}",
+            event.id,
+            res.1.next_cursor.expect("Expected a next_cursor")
+        ))
+        .await;
+        assert_eq!(res.0, 200);
+        assert_eq!(res.1.questions.len(), 1);
+        assert_eq!(res.1.questions[0].body, "no votes");
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_get_all_invalid_sort_params() {
+        let res: (u16, ErrorResponse) =
+            tests::test_get("/api/events/0/questions?sort=votes_cast").await;
+        assert_eq!(res.0, 400);
+        assert_eq!(
+            res.1.errors,
+            vec!["Unknown sort 'votes_cast', expected one of: created_at, updated_at, body, vote_count"]
+        );
+
+        let res: (u16, ErrorResponse) =
+            tests::test_get("/api/events/0/questions?direction=up").await;
+        assert_eq!(res.0, 400);
+        assert_eq!(
+            res.1.errors,
+            vec!["Unknown direction 'up', expected one of: asc, desc"]
+        );
+
+        let res: (u16, ErrorResponse) =
+            tests::test_get("/api/events/0/questions?created_after=yesterday").await;
+        assert_eq!(res.0, 400);
+        assert_eq!(
+            res.1.errors,
+            vec!["created_after must be an RFC 3339 timestamp"]
+        );
+
+        let res: (u16, ErrorResponse) =
+            tests::test_get("/api/events/0/questions?status=closed").await;
+        assert_eq!(res.0, 400);
+        assert_eq!(
+            res.1.errors,
+            vec!["Unknown status 'closed', expected one of: pending, open, rejected, pinned, highlighted, answered, archived"]
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_get_all_reports_every_invalid_param() {
+        let res: (u16, ErrorResponse) =
+            tests::test_get("/api/events/0/questions?limit=0&sort=votes_cast&cursor=nope").await;
+        assert_eq!(res.0, 400);
+        assert_eq!(res.1.errors.len(), 3);
+
+        let fields: Vec<(Option<&str>, &str)> = res
+            .1
+            .details
+            .iter()
+            .map(|detail| (detail.field.as_deref(), detail.code.as_str()))
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                (Some("limit"), "out_of_range"),
+                (Some("sort"), "invalid"),
+                (Some("cursor"), "invalid"),
+            ]
+        );
     }
 }
@@ -1,5 +1,21 @@
-mod get_all;
+mod approve;
 mod create;
+mod delete;
+mod get;
+mod get_all;
+mod reject;
+mod restore;
+mod search;
+mod update;
+mod update_status;
 
+pub use self::approve::*;
+pub use self::create::*;
+pub use self::delete::*;
+pub use self::get::*;
 pub use self::get_all::*;
-pub use self::create::*;
\ No newline at end of file
+pub use self::reject::*;
+pub use self::restore::*;
+pub use self::search::*;
+pub use self::update::*;
+pub use self::update_status::*;
@@ -0,0 +1,103 @@
+use actix::Addr;
+use actix_web::{
+    web::{block, Data, Json, Path},
+    Result,
+};
+
+use db::{
+    get_conn,
+    models::{Question, QuestionStatus},
+    PgPool,
+};
+use errors::Error;
+
+use crate::auth::EventModerator;
+use crate::websocket::{ChangedStatus, MessageToClient, Server, ServerEvent};
+
+pub async fn reject(
+    pool: Data<PgPool>,
+    websocket_srv: Data<Addr<Server>>,
+    path: Path<(i32, i32)>,
+    _moderator: EventModerator,
+) -> Result<Json<Question>, Error> {
+    let connection = get_conn(&pool)?;
+
+    let (event_id, question_id) = path.into_inner();
+    let res = block(move || Question::reject(&connection, event_id, question_id)).await?;
+    let question = res?;
+
+    // only moderators saw it while it was pending, so only they hear it was rejected
+    let changed = ChangedStatus {
+        question: question.clone(),
+        previous_status: QuestionStatus::Pending,
+    };
+    let msg = MessageToClient::new(event_id, ServerEvent::StatusChanged(changed));
+    websocket_srv.do_send(msg);
+
+    Ok(Json(question))
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::{self, RunQueryDsl};
+
+    use db::{
+        get_conn,
+        models::{Event, NewQuestion, Question, QuestionPage, QuestionStatus, Role},
+        new_pool,
+        schema::{events, questions, users},
+    };
+    use errors::ErrorResponse;
+
+    use crate::tests;
+
+    #[actix_rt::test]
+    pub async fn test_reject_question() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", true).unwrap();
+
+        let moderator = tests::sign_in_as(event.id, Role::Moderator).await;
+
+        let question = diesel::insert_into(questions::table)
+            .values(NewQuestion {
+                body: "A pending question".to_string(),
+                event_id: event.id,
+                status: QuestionStatus::Pending,
+                author_id: None,
+            })
+            .get_result::<Question>(&conn)
+            .unwrap();
+
+        let res: (u16, Question) = tests::test_post_as(
+            &format!("/api/events/{}/questions/{}/reject", event.id, question.id),
+            (),
+            &moderator,
+        )
+        .await;
+
+        assert_eq!(res.0, 200);
+        assert_eq!(res.1.status, QuestionStatus::Rejected);
+
+        let res: (u16, QuestionPage) = tests::test_get_as(
+            &format!("/api/events/{}/questions?status=rejected", event.id),
+            &moderator,
+        )
+        .await;
+        assert_eq!(res.0, 200);
+        assert_eq!(res.1.questions.len(), 1);
+        assert_eq!(res.1.questions[0].id, question.id);
+
+        let res: (u16, ErrorResponse) = tests::test_post_as(
+            &format!("/api/events/{}/questions/{}/approve", event.id, question.id),
+            (),
+            &moderator,
+        )
+        .await;
+        assert_eq!(res.0, 400);
+        assert_eq!(res.1.errors, vec!["Question is rejected, not pending"]);
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+}
@@ -0,0 +1,204 @@
+use actix::Addr;
+use actix_web::{
+    web::{block, Data, Json, Path},
+    Result,
+};
+
+use db::{get_conn, models::Question, PgPool};
+use errors::Error;
+
+use crate::auth::EventModerator;
+use crate::websocket::{MessageToClient, Server, ServerEvent};
+
+pub async fn restore(
+    pool: Data<PgPool>,
+    websocket_srv: Data<Addr<Server>>,
+    path: Path<(i32, i32)>,
+    _moderator: EventModerator,
+) -> Result<Json<Question>, Error> {
+    let connection = get_conn(&pool)?;
+
+    let (event_id, question_id) = path.into_inner();
+    let res = block(move || Question::restore(&connection, event_id, question_id)).await?;
+    let question = res?;
+
+    let msg = MessageToClient::new(event_id, ServerEvent::RestoredQuestion(question.clone()));
+    websocket_srv.do_send(msg);
+
+    Ok(Json(question))
+}
+
+#[cfg(test)]
+mod tests {
+    use awc::Client;
+    use diesel::{self, RunQueryDsl};
+
+    use db::{
+        get_conn,
+        models::{Event, NewQuestion, Question, QuestionPage, QuestionStatus, Role},
+        new_pool,
+        schema::{events, questions, users},
+    };
+    use errors::ErrorResponse;
+
+    use crate::tests;
+    use crate::websocket::ServerEvent;
+
+    #[actix_rt::test]
+    pub async fn test_restore_question() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+
+        let moderator = tests::sign_in_as(event.id, Role::Moderator).await;
+
+        let question = diesel::insert_into(questions::table)
+            .values(NewQuestion {
+                body: "A question to restore".to_string(),
+                event_id: event.id,
+                status: QuestionStatus::Open,
+                author_id: None,
+            })
+            .get_result::<Question>(&conn)
+            .unwrap();
+        Question::delete(&conn, event.id, question.id).unwrap();
+
+        let srv = tests::get_test_server();
+
+        let client = Client::default();
+        let ws_conn = client
+            .ws(tests::websocket_url(&srv, event.id))
+            .connect()
+            .await
+            .unwrap();
+
+        let mut res = srv
+            .post(format!(
+                "/api/events/{}/questions/{}/restore",
+                event.id, question.id
+            ))
+            .cookie(moderator.clone())
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(res.status().as_u16(), 200);
+
+        let restored: Question = res.json().await.unwrap();
+        assert_eq!(restored.id, question.id);
+        assert!(restored.deleted_at.is_none());
+
+        let mut stream = ws_conn.1;
+        let frame = tests::next_websocket_frame(&mut stream).await;
+
+        let data = tests::get_websocket_frame_data(frame);
+        let msg = data.expect("Message was not a string");
+        let question = match msg.event {
+            ServerEvent::RestoredQuestion(question) => question,
+            event => panic!("expected restoredquestion, got {:?}", event),
+        };
+        assert_eq!(question.id, restored.id);
+
+        drop(stream);
+
+        srv.stop().await;
+
+        let res: (u16, QuestionPage) =
+            tests::test_get(&format!("/api/events/{}/questions", event.id)).await;
+        assert_eq!(res.0, 200);
+        assert_eq!(res.1.questions.len(), 1);
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    pub async fn test_restore_pending_question_reaches_moderators() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "A moderated event", true).unwrap();
+
+        let moderator = tests::sign_in_as(event.id, Role::Moderator).await;
+
+        let question = diesel::insert_into(questions::table)
+            .values(NewQuestion {
+                body: "A pending question to restore".to_string(),
+                event_id: event.id,
+                status: QuestionStatus::Pending,
+                author_id: None,
+            })
+            .get_result::<Question>(&conn)
+            .unwrap();
+        Question::delete(&conn, event.id, question.id).unwrap();
+
+        let srv = tests::get_test_server();
+
+        let client = Client::default();
+        let (_, mut moderator_conn) = client
+            .ws(srv.url(&format!("/ws/?event_id={}", event.id)))
+            .cookie(moderator.clone())
+            .connect()
+            .await
+            .unwrap();
+
+        let res = srv
+            .post(format!(
+                "/api/events/{}/questions/{}/restore",
+                event.id, question.id
+            ))
+            .cookie(moderator)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+
+        let frame = tests::next_websocket_frame(&mut moderator_conn).await;
+        let msg = tests::get_websocket_frame_data(frame).unwrap();
+        match msg.event {
+            ServerEvent::RestoredQuestion(restored) => {
+                assert_eq!(restored.id, question.id);
+                assert_eq!(restored.status, QuestionStatus::Pending);
+            }
+            event => panic!("expected restoredquestion, got {:?}", event),
+        }
+
+        drop(moderator_conn);
+
+        srv.stop().await;
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    pub async fn test_restore_requires_deleted_question() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+
+        let moderator = tests::sign_in_as(event.id, Role::Moderator).await;
+
+        let question = diesel::insert_into(questions::table)
+            .values(NewQuestion {
+                body: "Not deleted".to_string(),
+                event_id: event.id,
+                status: QuestionStatus::Open,
+                author_id: None,
+            })
+            .get_result::<Question>(&conn)
+            .unwrap();
+
+        let res: (u16, ErrorResponse) = tests::test_post_as(
+            &format!("/api/events/{}/questions/{}/restore", event.id, question.id),
+            (),
+            &moderator,
+        )
+        .await;
+
+        assert_eq!(res.0, 404);
+        assert_eq!(res.1.errors, vec!["Record not found"]);
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+}
@@ -0,0 +1,144 @@
+use actix_web::{
+    web::{block, Data, Json, Path, Query},
+    Result,
+};
+use serde::{Deserialize, Serialize};
+
+use db::{
+    get_conn,
+    models::{Question, QuestionSearchResult},
+    PgPool,
+};
+use errors::Error;
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct SearchParams {
+    q: Option<String>,
+    limit: Option<i64>,
+}
+
+pub async fn search(
+    pool: Data<PgPool>,
+    event_id: Path<i32>,
+    params: Query<SearchParams>,
+) -> Result<Json<Vec<QuestionSearchResult>>, Error> {
+    let search = params.q.clone().unwrap_or_default();
+    let limit = params.limit;
+
+    let connection = get_conn(&pool)?;
+
+    let event_id = event_id.into_inner();
+    let res = block(move || Question::search(&connection, event_id, &search, limit)).await?;
+    let results = res?;
+
+    Ok(Json(results))
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::{self, RunQueryDsl};
+
+    use db::{
+        get_conn,
+        models::{Event, NewQuestion, QuestionSearchResult, QuestionStatus},
+        new_pool,
+        schema::{events, questions},
+    };
+    use errors::ErrorResponse;
+
+    use crate::tests;
+
+    #[actix_rt::test]
+    async fn test_search_ranks_matching_questions() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+
+        for body in &[
+            "How do we deploy the server?",
+            "Deploying is hard, who deploys on fridays?",
+            "What is for lunch?",
+        ] {
+            diesel::insert_into(questions::table)
+                .values(NewQuestion {
+                    body: body.to_string(),
+                    event_id: event.id,
+                    status: QuestionStatus::Open,
+                    author_id: None,
+                })
+                .execute(&conn)
+                .unwrap();
+        }
+
+        // only visible questions are searched
+        diesel::insert_into(questions::table)
+            .values(NewQuestion {
+                body: "Should we deploy before approving this?".to_string(),
+                event_id: event.id,
+                status: QuestionStatus::Pending,
+                author_id: None,
+            })
+            .execute(&conn)
+            .unwrap();
+
+        let res: (u16, Vec<QuestionSearchResult>) = tests::test_get(&format!(
+            "/api/events/{}/questions/search?q=deploy",
+            event.id
+        ))
+        .await;
+        assert_eq!(res.0, 200);
+        assert_eq!(res.1.len(), 2);
+        assert_eq!(
+            res.1[0].question.body,
+            "Deploying is hard, who deploys on fridays?"
+        );
+        assert!(res.1[0].rank > res.1[1].rank);
+        assert_eq!(
+            res.1[1].headline,
+            "How do we <mark>deploy</mark> the server?"
+        );
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_search_escapes_headlines() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+
+        diesel::insert_into(questions::table)
+            .values(NewQuestion {
+                body: "Can we deploy <img src=x onerror=alert(1)> & friends?".to_string(),
+                event_id: event.id,
+                status: QuestionStatus::Open,
+                author_id: None,
+            })
+            .execute(&conn)
+            .unwrap();
+
+        let res: (u16, Vec<QuestionSearchResult>) = tests::test_get(&format!(
+            "/api/events/{}/questions/search?q=deploy",
+            event.id
+        ))
+        .await;
+        assert_eq!(res.0, 200);
+        assert_eq!(
+            res.1[0].headline,
+            "Can we <mark>deploy</mark> &lt;img src=x onerror=alert(1)&gt; &amp; friends?"
+        );
+        assert_eq!(
+            res.1[0].question.body,
+            "Can we deploy <img src=x onerror=alert(1)> & friends?"
+        );
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_search_requires_query() {
+        let res: (u16, ErrorResponse) = tests::test_get("/api/events/0/questions/search?q=").await;
+        assert_eq!(res.0, 400);
+        assert_eq!(res.1.errors, vec!["q is required"]);
+    }
+}
@@ -0,0 +1,286 @@
+use actix::Addr;
+use actix_web::{
+    web::{block, Data, Json, Path},
+    Result,
+};
+use serde::{Deserialize, Serialize};
+
+use db::{
+    get_conn,
+    models::{Event, Question},
+    PgPool,
+};
+use errors::{Error, Validator};
+
+use crate::auth::{is_moderator, require_author_or_moderator, CurrentUser};
+use crate::websocket::{MessageToClient, Server, ServerEvent};
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct UpdateRequest {
+    body: String,
+}
+
+/// Edits a question's body, for its author or a moderator. In a moderated event, authors can
+/// only edit questions still waiting on a moderator, otherwise what they change the text to
+/// would reach participants without being approved
+pub async fn update(
+    pool: Data<PgPool>,
+    websocket_srv: Data<Addr<Server>>,
+    path: Path<(i32, i32)>,
+    user: CurrentUser,
+    params: Json<UpdateRequest>,
+) -> Result<Json<Question>, Error> {
+    Validator::new()
+        .required("body", &params.body, "Body is required")
+        .finish()?;
+
+    let connection = get_conn(&pool)?;
+
+    let (event_id, question_id) = path.into_inner();
+    let res = block(move || {
+        let question = Question::find(&connection, event_id, question_id)?;
+        require_author_or_moderator(&connection, user, &question)?;
+        if Event::find(&connection, event_id)?.moderated
+            && !is_moderator(&connection, user, event_id)?
+        {
+            Question::update_pending(&connection, event_id, question_id, &params.body)
+        } else {
+            Question::update(&connection, event_id, question_id, &params.body)
+        }
+    })
+    .await?;
+    let question = res?;
+
+    let msg = MessageToClient::new(event_id, ServerEvent::UpdatedQuestion(question.clone()));
+    websocket_srv.do_send(msg);
+
+    Ok(Json(question))
+}
+
+#[cfg(test)]
+mod tests {
+    use awc::Client;
+    use diesel::{self, RunQueryDsl};
+
+    use db::{
+        get_conn,
+        models::{Event, NewQuestion, Question, QuestionStatus, Role, User},
+        new_pool,
+        schema::{events, questions, users},
+    };
+    use errors::ErrorResponse;
+
+    use super::UpdateRequest;
+    use crate::tests;
+    use crate::websocket::ServerEvent;
+
+    #[actix_rt::test]
+    pub async fn test_update_question() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+
+        let moderator = tests::sign_in_as(event.id, Role::Moderator).await;
+
+        let question = diesel::insert_into(questions::table)
+            .values(NewQuestion {
+                body: "A question with a tpyo".to_string(),
+                event_id: event.id,
+                status: QuestionStatus::Open,
+                author_id: None,
+            })
+            .get_result::<Question>(&conn)
+            .unwrap();
+
+        let srv = tests::get_test_server();
+
+        let client = Client::default();
+        let ws_conn = client
+            .ws(tests::websocket_url(&srv, event.id))
+            .connect()
+            .await
+            .unwrap();
+
+        let mut res = srv
+            .patch(format!(
+                "/api/events/{}/questions/{}",
+                event.id, question.id
+            ))
+            .cookie(moderator.clone())
+            .send_json(&NewQuestion {
+                body: "A question with a typo".to_string(),
+                event_id: event.id,
+                status: QuestionStatus::Open,
+                author_id: None,
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(res.status().as_u16(), 200);
+
+        let updated: Question = res.json().await.unwrap();
+        assert_eq!(updated.id, question.id);
+        assert_eq!(updated.body, "A question with a typo");
+        assert!(updated.updated_at > question.updated_at);
+
+        let mut stream = ws_conn.1;
+        let frame = tests::next_websocket_frame(&mut stream).await;
+
+        let data = tests::get_websocket_frame_data(frame);
+        let msg = data.expect("Message was not a string");
+        let question = match msg.event {
+            ServerEvent::UpdatedQuestion(question) => question,
+            event => panic!("expected updatedquestion, got {:?}", event),
+        };
+        assert_eq!(question.body, "A question with a typo");
+
+        drop(stream);
+
+        srv.stop().await;
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    pub async fn test_update_body_required() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+
+        let moderator = tests::sign_in_as(event.id, Role::Moderator).await;
+
+        let res: (u16, ErrorResponse) = tests::test_patch_as(
+            &format!("/api/events/{}/questions/0", event.id),
+            NewQuestion {
+                body: "".to_string(),
+                event_id: event.id,
+                status: QuestionStatus::Open,
+                author_id: None,
+            },
+            &moderator,
+        )
+        .await;
+
+        assert_eq!(res.0, 400);
+        assert_eq!(res.1.errors, vec!["Body is required"]);
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    pub async fn test_update_not_found() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+
+        let moderator = tests::sign_in_as(event.id, Role::Moderator).await;
+
+        let res: (u16, ErrorResponse) = tests::test_patch_as(
+            &format!("/api/events/{}/questions/0", event.id),
+            NewQuestion {
+                body: "Missing".to_string(),
+                event_id: event.id,
+                status: QuestionStatus::Open,
+                author_id: None,
+            },
+            &moderator,
+        )
+        .await;
+
+        assert_eq!(res.0, 404);
+        assert_eq!(res.1.errors, vec!["Record not found"]);
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    pub async fn test_update_requires_sign_in() {
+        let res: (u16, ErrorResponse) = tests::test_patch(
+            "/api/events/0/questions/0",
+            NewQuestion {
+                body: "Anonymous edit".to_string(),
+                event_id: 0,
+                status: QuestionStatus::Open,
+                author_id: None,
+            },
+        )
+        .await;
+
+        assert_eq!(res.0, 401);
+        assert_eq!(res.1.errors, vec!["Unauthorized"]);
+    }
+
+    #[actix_rt::test]
+    pub async fn test_update_own_question() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+
+        let author = User::create(&conn, "update-author", "correct horse").unwrap();
+        let question =
+            Question::create(&conn, event.id, Some(author.id), &"My tpyo".to_string()).unwrap();
+        let route = format!("/api/events/{}/questions/{}", event.id, question.id);
+        let params = UpdateRequest {
+            body: "My typo".to_string(),
+        };
+
+        let participant = tests::sign_in_as(event.id, Role::Participant).await;
+        let res: (u16, ErrorResponse) = tests::test_patch_as(&route, &params, &participant).await;
+        assert_eq!(res.0, 403);
+        assert_eq!(res.1.errors, vec!["Forbidden"]);
+        let unchanged = Question::find(&conn, event.id, question.id).unwrap();
+        assert_eq!(unchanged.body, "My tpyo");
+
+        let cookie = tests::sign_in("update-author", "correct horse").await;
+        let res: (u16, Question) = tests::test_patch_as(&route, &params, &cookie).await;
+        assert_eq!(res.0, 200);
+        assert_eq!(res.1.body, "My typo");
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    pub async fn test_update_moderated_question() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "A moderated event", true).unwrap();
+
+        let author = User::create(&conn, "moderated-author", "correct horse").unwrap();
+        let question =
+            Question::create(&conn, event.id, Some(author.id), &"Harmless".to_string()).unwrap();
+        let route = format!("/api/events/{}/questions/{}", event.id, question.id);
+        let edit = |body: &str| UpdateRequest {
+            body: body.to_string(),
+        };
+
+        // still waiting on a moderator, so the new text will be seen by one first
+        let cookie = tests::sign_in("moderated-author", "correct horse").await;
+        let res: (u16, Question) =
+            tests::test_patch_as(&route, edit("Still harmless"), &cookie).await;
+        assert_eq!(res.0, 200);
+        assert_eq!(res.1.status, QuestionStatus::Pending);
+
+        Question::approve(&conn, event.id, question.id).unwrap();
+
+        let res: (u16, ErrorResponse) =
+            tests::test_patch_as(&route, edit("Something else entirely"), &cookie).await;
+        assert_eq!(res.0, 409);
+        assert_eq!(res.1.errors, vec!["Question has already been moderated"]);
+        let unchanged = Question::find(&conn, event.id, question.id).unwrap();
+        assert_eq!(unchanged.body, "Still harmless");
+
+        let moderator = tests::sign_in_as(event.id, Role::Moderator).await;
+        let res: (u16, Question) =
+            tests::test_patch_as(&route, edit("Tidied up"), &moderator).await;
+        assert_eq!(res.0, 200);
+        assert_eq!(res.1.body, "Tidied up");
+        assert_eq!(res.1.status, QuestionStatus::Open);
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+}
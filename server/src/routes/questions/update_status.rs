@@ -0,0 +1,259 @@
+use actix::Addr;
+use actix_web::{
+    web::{block, Data, Json, Path},
+    Result,
+};
+use serde::{Deserialize, Serialize};
+
+use db::{
+    get_conn,
+    models::{Question, QuestionStatus},
+    PgPool,
+};
+use errors::Error;
+
+use crate::auth::EventModerator;
+use crate::websocket::{ChangedStatus, MessageToClient, Server, ServerEvent};
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct StatusRequest {
+    status: String,
+}
+
+pub async fn update_status(
+    pool: Data<PgPool>,
+    websocket_srv: Data<Addr<Server>>,
+    path: Path<(i32, i32)>,
+    _moderator: EventModerator,
+    params: Json<StatusRequest>,
+) -> Result<Json<Question>, Error> {
+    let status = QuestionStatus::parse(&params.status)?;
+
+    let connection = get_conn(&pool)?;
+
+    let (event_id, question_id) = path.into_inner();
+    let res =
+        block(move || Question::set_status(&connection, event_id, question_id, status)).await?;
+    let (question, previous_status) = res?;
+
+    let changed = ChangedStatus {
+        question: question.clone(),
+        previous_status,
+    };
+    let msg = MessageToClient::new(event_id, ServerEvent::StatusChanged(changed));
+    websocket_srv.do_send(msg);
+
+    Ok(Json(question))
+}
+
+#[cfg(test)]
+mod tests {
+    use awc::Client;
+    use diesel::{self, RunQueryDsl};
+
+    use db::{
+        get_conn,
+        models::{Event, NewQuestion, Question, QuestionPage, QuestionStatus, Role},
+        new_pool,
+        schema::{events, questions, users},
+    };
+    use errors::ErrorResponse;
+
+    use super::StatusRequest;
+    use crate::tests;
+    use crate::websocket::ServerEvent;
+
+    #[actix_rt::test]
+    pub async fn test_update_status() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+
+        let moderator = tests::sign_in_as(event.id, Role::Moderator).await;
+
+        let question = diesel::insert_into(questions::table)
+            .values(NewQuestion {
+                body: "A question for the stage".to_string(),
+                event_id: event.id,
+                status: QuestionStatus::Open,
+                author_id: None,
+            })
+            .get_result::<Question>(&conn)
+            .unwrap();
+
+        let srv = tests::get_test_server();
+
+        let client = Client::default();
+        let ws_conn = client
+            .ws(tests::websocket_url(&srv, event.id))
+            .connect()
+            .await
+            .unwrap();
+
+        let mut res = srv
+            .post(format!(
+                "/api/events/{}/questions/{}/status",
+                event.id, question.id
+            ))
+            .cookie(moderator.clone())
+            .send_json(&StatusRequest {
+                status: "pinned".to_string(),
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(res.status().as_u16(), 200);
+
+        let pinned: Question = res.json().await.unwrap();
+        assert_eq!(pinned.status, QuestionStatus::Pinned);
+
+        let mut stream = ws_conn.1;
+        let frame = tests::next_websocket_frame(&mut stream).await;
+
+        let data = tests::get_websocket_frame_data(frame);
+        let msg = data.expect("Message was not a string");
+        let changed = match msg.event {
+            ServerEvent::StatusChanged(changed) => changed,
+            event => panic!("expected statuschanged, got {:?}", event),
+        };
+        assert_eq!(changed.previous_status, QuestionStatus::Open);
+        let question = changed.question;
+        assert_eq!(question.id, pinned.id);
+        assert_eq!(question.status, QuestionStatus::Pinned);
+
+        drop(stream);
+
+        srv.stop().await;
+
+        let res: (u16, QuestionPage) =
+            tests::test_get(&format!("/api/events/{}/questions", event.id)).await;
+        assert_eq!(res.0, 200);
+        assert_eq!(res.1.questions.len(), 1);
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    pub async fn test_archiving_reaches_participants() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+
+        let moderator = tests::sign_in_as(event.id, Role::Moderator).await;
+
+        let question = diesel::insert_into(questions::table)
+            .values(NewQuestion {
+                body: "A question that's done with".to_string(),
+                event_id: event.id,
+                status: QuestionStatus::Open,
+                author_id: None,
+            })
+            .get_result::<Question>(&conn)
+            .unwrap();
+
+        let srv = tests::get_test_server();
+
+        let client = Client::default();
+        let (_, mut participant) = client
+            .ws(tests::websocket_url(&srv, event.id))
+            .connect()
+            .await
+            .unwrap();
+
+        let res = srv
+            .post(format!(
+                "/api/events/{}/questions/{}/status",
+                event.id, question.id
+            ))
+            .cookie(moderator.clone())
+            .send_json(&StatusRequest {
+                status: "archived".to_string(),
+            })
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+
+        // participants can't see it any more, but they still need to take it down
+        let frame = tests::next_websocket_frame(&mut participant).await;
+        let msg = tests::get_websocket_frame_data(frame).expect("Message was not a string");
+        let changed = match msg.event {
+            ServerEvent::StatusChanged(changed) => changed,
+            event => panic!("expected statuschanged, got {:?}", event),
+        };
+        assert_eq!(changed.question.id, question.id);
+        assert_eq!(changed.question.status, QuestionStatus::Archived);
+        assert_eq!(changed.previous_status, QuestionStatus::Open);
+
+        drop(participant);
+
+        srv.stop().await;
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    pub async fn test_update_status_invalid_transition() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+
+        let moderator = tests::sign_in_as(event.id, Role::Moderator).await;
+
+        let question = diesel::insert_into(questions::table)
+            .values(NewQuestion {
+                body: "A finished question".to_string(),
+                event_id: event.id,
+                status: QuestionStatus::Archived,
+                author_id: None,
+            })
+            .get_result::<Question>(&conn)
+            .unwrap();
+
+        let res: (u16, QuestionPage) =
+            tests::test_get(&format!("/api/events/{}/questions", event.id)).await;
+        assert_eq!(res.0, 200);
+        assert_eq!(res.1.questions.len(), 0);
+
+        let route = format!("/api/events/{}/questions/{}/status", event.id, question.id);
+
+        let res: (u16, ErrorResponse) = tests::test_post_as(
+            &route,
+            StatusRequest {
+                status: "pinned".to_string(),
+            },
+            &moderator,
+        )
+        .await;
+        assert_eq!(res.0, 400);
+        assert_eq!(
+            res.1.errors,
+            vec!["Cannot move question from archived to pinned"]
+        );
+
+        let res: (u16, ErrorResponse) = tests::test_post_as(
+            &route,
+            StatusRequest {
+                status: "closed".to_string(),
+            },
+            &moderator,
+        )
+        .await;
+        assert_eq!(res.0, 400);
+
+        let res: (u16, Question) = tests::test_post_as(
+            &route,
+            StatusRequest {
+                status: "open".to_string(),
+            },
+            &moderator,
+        )
+        .await;
+        assert_eq!(res.0, 200);
+        assert_eq!(res.1.status, QuestionStatus::Open);
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+}
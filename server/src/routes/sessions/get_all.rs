@@ -0,0 +1,80 @@
+use actix::Addr;
+use actix_web::{
+    web::{Data, Json, Path},
+    Result,
+};
+
+use errors::Error;
+
+use crate::auth::EventHost;
+use crate::websocket::{GetSessionStats, Server, SessionStats};
+
+/// The open websockets and streams on an event, with how many messages each has dropped
+pub async fn get_all(
+    websocket_srv: Data<Addr<Server>>,
+    event_id: Path<i32>,
+    _host: EventHost,
+) -> Result<Json<Vec<SessionStats>>, Error> {
+    let event_id = event_id.into_inner();
+    let stats = websocket_srv
+        .send(GetSessionStats { event_id })
+        .await
+        .map_err(|err| Error::InternalServerError(err.to_string()))?;
+
+    Ok(Json(stats))
+}
+
+#[cfg(test)]
+mod tests {
+    use awc::Client;
+    use diesel::{self, RunQueryDsl};
+
+    use db::{
+        get_conn,
+        models::{Event, Role},
+        new_pool,
+        schema::{events, users},
+    };
+    use errors::ErrorResponse;
+
+    use crate::tests;
+    use crate::websocket::SessionStats;
+
+    #[actix_rt::test]
+    async fn test_get_all_sessions() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+
+        let host = tests::sign_in_as(event.id, Role::Host).await;
+        let moderator = tests::sign_in_as(event.id, Role::Moderator).await;
+
+        let srv = tests::get_test_server();
+        let client = Client::default();
+        let (_, ws_conn) = client
+            .ws(tests::websocket_url(&srv, event.id))
+            .connect()
+            .await
+            .unwrap();
+
+        let route = format!("/api/events/{}/sessions", event.id);
+
+        let mut res = srv.get(&route).cookie(host.clone()).send().await.unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        let stats: Vec<SessionStats> = res.json().await.unwrap();
+        assert_eq!(stats.len(), 1);
+        assert_eq!((stats[0].dropped, stats[0].total_dropped), (0, 0));
+
+        let res = srv.get(&route).cookie(moderator).send().await.unwrap();
+        assert_eq!(res.status().as_u16(), 403);
+
+        let res: (u16, ErrorResponse) = tests::test_get(&route).await;
+        assert_eq!(res.0, 401);
+
+        drop(ws_conn);
+        srv.stop().await;
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+}
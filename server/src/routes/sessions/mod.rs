@@ -0,0 +1,3 @@
+mod get_all;
+
+pub use self::get_all::*;
@@ -0,0 +1,222 @@
+use actix::Addr;
+use actix_web::{
+    web::{block, Data, Json, Path},
+    Result,
+};
+
+use db::{
+    get_conn,
+    models::{Question, Vote},
+    PgPool,
+};
+use errors::Error;
+
+use crate::auth::CurrentUser;
+use crate::websocket::{MessageToClient, Server, ServerEvent};
+
+/// Votes as the signed in user, who can vote for each question once
+pub async fn create(
+    pool: Data<PgPool>,
+    websocket_srv: Data<Addr<Server>>,
+    path: Path<(i32, i32)>,
+    user: CurrentUser,
+) -> Result<Json<Question>, Error> {
+    let connection = get_conn(&pool)?;
+
+    let (event_id, question_id) = path.into_inner();
+    let res = block(move || Vote::create(&connection, event_id, question_id, user.id)).await?;
+    let question = res?;
+
+    let msg = MessageToClient::new(event_id, ServerEvent::VoteChanged(question.clone()));
+    websocket_srv.do_send(msg);
+
+    Ok(Json(question))
+}
+
+#[cfg(test)]
+mod tests {
+    use awc::Client;
+    use diesel::{self, RunQueryDsl};
+
+    use db::{
+        get_conn,
+        models::{Event, NewQuestion, Question, QuestionStatus, User, Vote},
+        new_pool,
+        schema::{events, questions, users, votes},
+    };
+    use errors::ErrorResponse;
+
+    use crate::tests;
+    use crate::websocket::ServerEvent;
+
+    #[actix_rt::test]
+    pub async fn test_create_vote() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+
+        let question = diesel::insert_into(questions::table)
+            .values(NewQuestion {
+                body: "Vote for me".to_string(),
+                event_id: event.id,
+                status: QuestionStatus::Open,
+                author_id: None,
+            })
+            .get_result::<Question>(&conn)
+            .unwrap();
+
+        let voter = User::create(&conn, "voter-one", "correct horse").unwrap();
+        let cookie = tests::sign_in("voter-one", "correct horse").await;
+
+        let srv = tests::get_test_server();
+
+        let client = Client::default();
+        let ws_conn = client
+            .ws(tests::websocket_url(&srv, event.id))
+            .connect()
+            .await
+            .unwrap();
+
+        let mut res = srv
+            .post(format!(
+                "/api/events/{}/questions/{}/vote",
+                event.id, question.id
+            ))
+            .cookie(cookie)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(res.status().as_u16(), 200);
+
+        let voted: Question = res.json().await.unwrap();
+        assert_eq!(voted.id, question.id);
+        assert_eq!(voted.vote_count, 1);
+
+        let mut stream = ws_conn.1;
+        let frame = tests::next_websocket_frame(&mut stream).await;
+
+        let data = tests::get_websocket_frame_data(frame);
+        let msg = data.expect("Message was not a string");
+        let question = match msg.event {
+            ServerEvent::VoteChanged(question) => question,
+            event => panic!("expected votechanged, got {:?}", event),
+        };
+        assert_eq!(question.vote_count, 1);
+
+        drop(stream);
+
+        srv.stop().await;
+
+        let result_votes = votes::dsl::votes.load::<Vote>(&conn).unwrap();
+        assert_eq!(result_votes.len(), 1);
+        assert_eq!(result_votes[0].user_id, voter.id);
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    pub async fn test_create_vote_once_per_voter() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+
+        let question = diesel::insert_into(questions::table)
+            .values(NewQuestion {
+                body: "Vote for me".to_string(),
+                event_id: event.id,
+                status: QuestionStatus::Open,
+                author_id: None,
+            })
+            .get_result::<Question>(&conn)
+            .unwrap();
+
+        let voter = User::create(&conn, "voter-twice", "correct horse").unwrap();
+        let cookie = tests::sign_in("voter-twice", "correct horse").await;
+        Vote::create(&conn, event.id, question.id, voter.id).unwrap();
+
+        let res: (u16, ErrorResponse) = tests::test_post_as(
+            &format!("/api/events/{}/questions/{}/vote", event.id, question.id),
+            (),
+            &cookie,
+        )
+        .await;
+
+        assert_eq!(res.0, 409);
+        assert_eq!(res.1.errors, vec!["Already exists"]);
+
+        let question = Question::find(&conn, event.id, question.id).unwrap();
+        assert_eq!(question.vote_count, 1);
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    pub async fn test_create_vote_requires_user() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+
+        let question = Question::create(&conn, event.id, None, &"Vote for me".to_string()).unwrap();
+
+        let res: (u16, ErrorResponse) = tests::test_post(
+            &format!("/api/events/{}/questions/{}/vote", event.id, question.id),
+            (),
+        )
+        .await;
+        assert_eq!(res.0, 401);
+
+        let question = Question::find(&conn, event.id, question.id).unwrap();
+        assert_eq!(question.vote_count, 0);
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    pub async fn test_create_vote_question_not_found() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+
+        User::create(&conn, "voter-lost", "correct horse").unwrap();
+        let cookie = tests::sign_in("voter-lost", "correct horse").await;
+
+        let res: (u16, ErrorResponse) =
+            tests::test_post_as("/api/events/0/questions/0/vote", (), &cookie).await;
+
+        assert_eq!(res.0, 404);
+        assert_eq!(res.1.errors, vec!["Record not found"]);
+
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    pub async fn test_create_vote_question_not_visible() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", true).unwrap();
+
+        let question = Question::create(&conn, event.id, None, &"Not yet".to_string()).unwrap();
+        assert_eq!(question.status, QuestionStatus::Pending);
+
+        User::create(&conn, "voter-early", "correct horse").unwrap();
+        let cookie = tests::sign_in("voter-early", "correct horse").await;
+
+        let res: (u16, ErrorResponse) = tests::test_post_as(
+            &format!("/api/events/{}/questions/{}/vote", event.id, question.id),
+            (),
+            &cookie,
+        )
+        .await;
+
+        assert_eq!(res.0, 404);
+        assert_eq!(res.1.errors, vec!["Record not found"]);
+
+        let question = Question::find(&conn, event.id, question.id).unwrap();
+        assert_eq!(question.vote_count, 0);
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+}
@@ -0,0 +1,165 @@
+use actix::Addr;
+use actix_web::{
+    web::{block, Data, Json, Path},
+    Result,
+};
+
+use db::{
+    get_conn,
+    models::{Question, Vote},
+    PgPool,
+};
+use errors::Error;
+
+use crate::auth::CurrentUser;
+use crate::websocket::{MessageToClient, Server, ServerEvent};
+
+/// Takes back the signed in user's vote
+pub async fn delete(
+    pool: Data<PgPool>,
+    websocket_srv: Data<Addr<Server>>,
+    path: Path<(i32, i32)>,
+    user: CurrentUser,
+) -> Result<Json<Question>, Error> {
+    let connection = get_conn(&pool)?;
+
+    let (event_id, question_id) = path.into_inner();
+    let res = block(move || Vote::delete(&connection, event_id, question_id, user.id)).await?;
+    let question = res?;
+
+    let msg = MessageToClient::new(event_id, ServerEvent::VoteChanged(question.clone()));
+    websocket_srv.do_send(msg);
+
+    Ok(Json(question))
+}
+
+#[cfg(test)]
+mod tests {
+    use awc::Client;
+    use diesel::{self, RunQueryDsl};
+
+    use db::{
+        get_conn,
+        models::{Event, NewQuestion, Question, QuestionStatus, User, Vote},
+        new_pool,
+        schema::{events, questions, users, votes},
+    };
+    use errors::ErrorResponse;
+
+    use crate::tests;
+    use crate::websocket::ServerEvent;
+
+    #[actix_rt::test]
+    pub async fn test_delete_vote() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+
+        let question = diesel::insert_into(questions::table)
+            .values(NewQuestion {
+                body: "Vote for me".to_string(),
+                event_id: event.id,
+                status: QuestionStatus::Open,
+                author_id: None,
+            })
+            .get_result::<Question>(&conn)
+            .unwrap();
+
+        let voter = User::create(&conn, "unvoter", "correct horse").unwrap();
+        let cookie = tests::sign_in("unvoter", "correct horse").await;
+        Vote::create(&conn, event.id, question.id, voter.id).unwrap();
+
+        let srv = tests::get_test_server();
+
+        let client = Client::default();
+        let ws_conn = client
+            .ws(tests::websocket_url(&srv, event.id))
+            .connect()
+            .await
+            .unwrap();
+
+        let mut res = srv
+            .delete(format!(
+                "/api/events/{}/questions/{}/vote",
+                event.id, question.id
+            ))
+            .cookie(cookie)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(res.status().as_u16(), 200);
+
+        let unvoted: Question = res.json().await.unwrap();
+        assert_eq!(unvoted.vote_count, 0);
+
+        let mut stream = ws_conn.1;
+        let frame = tests::next_websocket_frame(&mut stream).await;
+
+        let data = tests::get_websocket_frame_data(frame);
+        let msg = data.expect("Message was not a string");
+        let question = match msg.event {
+            ServerEvent::VoteChanged(question) => question,
+            event => panic!("expected votechanged, got {:?}", event),
+        };
+        assert_eq!(question.vote_count, 0);
+
+        drop(stream);
+
+        srv.stop().await;
+
+        let result_votes = votes::dsl::votes.load::<Vote>(&conn).unwrap();
+        assert_eq!(result_votes.len(), 0);
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    pub async fn test_delete_vote_not_found() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+
+        let question = diesel::insert_into(questions::table)
+            .values(NewQuestion {
+                body: "Vote for me".to_string(),
+                event_id: event.id,
+                status: QuestionStatus::Open,
+                author_id: None,
+            })
+            .get_result::<Question>(&conn)
+            .unwrap();
+
+        // someone else's vote can't be taken back
+        let voter = User::create(&conn, "other-voter", "correct horse").unwrap();
+        Vote::create(&conn, event.id, question.id, voter.id).unwrap();
+
+        User::create(&conn, "not-a-voter", "correct horse").unwrap();
+        let cookie = tests::sign_in("not-a-voter", "correct horse").await;
+
+        let srv = tests::get_test_server();
+
+        let mut res = srv
+            .delete(format!(
+                "/api/events/{}/questions/{}/vote",
+                event.id, question.id
+            ))
+            .cookie(cookie)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(res.status().as_u16(), 404);
+        let error: ErrorResponse = res.json().await.unwrap();
+        assert_eq!(error.errors, vec!["Vote not found"]);
+
+        srv.stop().await;
+
+        let question = Question::find(&conn, event.id, question.id).unwrap();
+        assert_eq!(question.vote_count, 1);
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+}
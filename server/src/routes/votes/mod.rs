@@ -0,0 +1,5 @@
+mod create;
+mod delete;
+
+pub use self::create::*;
+pub use self::delete::*;
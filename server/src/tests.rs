@@ -2,19 +2,39 @@ use actix::Actor;
 use actix_http::Request;
 use actix_service::Service;
 use actix_test;
-use actix_web::{body::BoxBody, dev::ServiceResponse, error::Error, test, web, App};
+use actix_web::{
+    body::{BoxBody, EitherBody},
+    cookie::Cookie,
+    dev::ServiceResponse,
+    error::Error,
+    test, web, App,
+};
 use actix_web_actors::ws;
+use chrono::{Duration, Utc};
+use futures::{Stream, StreamExt};
 use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
 
+use db::{
+    get_conn,
+    models::{EventMember, Role, User},
+};
+
+use crate::auth::{identity_service, CurrentUser, TokenKey, TOKEN_LIFETIME_SECONDS};
 use crate::routes::routes;
-use crate::websocket::{MessageToClient, Server};
+use crate::websocket::{CommandReply, MessageToClient, Server};
+
+pub const SECRET_KEY: &[u8] = b"a test secret key that is long enough";
 
 pub async fn get_service(
-) -> impl Service<Request, Response = ServiceResponse<BoxBody>, Error = Error> {
+) -> impl Service<Request, Response = ServiceResponse<EitherBody<BoxBody>>, Error = Error> {
     test::init_service(
         App::new()
             .app_data(web::Data::new(db::new_pool()))
-            .app_data(web::Data::new(Server::new().start()))
+            .app_data(web::Data::new(Server::new(db::new_pool()).start()))
+            .app_data(web::Data::new(TokenKey::new(SECRET_KEY)))
+            .wrap(identity_service(SECRET_KEY, false))
             .configure(routes),
     )
     .await
@@ -24,19 +44,17 @@ pub fn get_test_server() -> actix_test::TestServer {
     actix_test::start(|| {
         App::new()
             .app_data(web::Data::new(db::new_pool()))
-            .app_data(web::Data::new(Server::new().start()))
+            .app_data(web::Data::new(Server::new(db::new_pool()).start()))
+            .app_data(web::Data::new(TokenKey::new(SECRET_KEY)))
+            .wrap(identity_service(SECRET_KEY, false))
             .configure(routes)
     })
 }
 
-pub async fn test_get<R>(route: &str) -> (u16, R)
+async fn read_response_json<R>(res: ServiceResponse<EitherBody<BoxBody>>) -> (u16, R)
 where
     R: DeserializeOwned,
 {
-    let mut app = get_service().await;
-    let req = test::TestRequest::get().uri(route);
-    let res = test::call_service(&mut app, req.to_request()).await;
-
     let status = res.status().as_u16();
     let body = test::read_body(res).await;
     let json_body = serde_json::from_slice(&body).unwrap_or_else(|_| {
@@ -51,6 +69,17 @@ where
     (status, json_body)
 }
 
+pub async fn test_get<R>(route: &str) -> (u16, R)
+where
+    R: DeserializeOwned,
+{
+    let mut app = get_service().await;
+    let req = test::TestRequest::get().uri(route);
+    let res = test::call_service(&mut app, req.to_request()).await;
+
+    read_response_json(res).await
+}
+
 pub async fn test_post<T: Serialize, R>(route: &str, params: T) -> (u16, R)
 where
     R: DeserializeOwned,
@@ -61,18 +90,149 @@ where
 
     let res = test::call_service(&mut app, req.to_request()).await;
 
-    let status = res.status().as_u16();
-    let body = test::read_body(res).await;
-    let json_body = serde_json::from_slice(&body).unwrap_or_else(|_| {
-        panic!(
-            "read_response_json failed during deserialization. response: {} status: {}",
-            String::from_utf8(body.to_vec())
-                .unwrap_or_else(|_| "Could not convert Bytes -> String".to_string()),
-            status
-        )
-    });
+    read_response_json(res).await
+}
 
-    (status, json_body)
+pub async fn test_patch<T: Serialize, R>(route: &str, params: T) -> (u16, R)
+where
+    R: DeserializeOwned,
+{
+    let mut app = get_service().await;
+
+    let req = test::TestRequest::patch().set_json(&params).uri(route);
+
+    let res = test::call_service(&mut app, req.to_request()).await;
+
+    read_response_json(res).await
+}
+
+/// Signs in through the login route and returns the session cookie
+pub async fn sign_in(username: &str, password: &str) -> Cookie<'static> {
+    let app = get_service().await;
+
+    let req = test::TestRequest::post()
+        .set_json(serde_json::json!({ "username": username, "password": password }))
+        .uri("/api/auth/login");
+
+    let res = test::call_service(&app, req.to_request()).await;
+    assert_eq!(res.status().as_u16(), 200, "sign in failed");
+
+    let cookie = res
+        .response()
+        .cookies()
+        .find(|cookie| cookie.name() == "auth")
+        .expect("sign in did not set a cookie");
+
+    cookie.into_owned()
+}
+
+pub async fn test_get_as<R>(route: &str, cookie: &Cookie<'_>) -> (u16, R)
+where
+    R: DeserializeOwned,
+{
+    let app = get_service().await;
+    let req = test::TestRequest::get()
+        .uri(route)
+        .cookie(cookie.clone().into_owned());
+    let res = test::call_service(&app, req.to_request()).await;
+
+    read_response_json(res).await
+}
+
+pub async fn test_post_as<T: Serialize, R>(route: &str, params: T, cookie: &Cookie<'_>) -> (u16, R)
+where
+    R: DeserializeOwned,
+{
+    let app = get_service().await;
+
+    let req = test::TestRequest::post()
+        .set_json(&params)
+        .uri(route)
+        .cookie(cookie.clone().into_owned());
+
+    let res = test::call_service(&app, req.to_request()).await;
+
+    read_response_json(res).await
+}
+
+pub async fn test_patch_as<T: Serialize, R>(route: &str, params: T, cookie: &Cookie<'_>) -> (u16, R)
+where
+    R: DeserializeOwned,
+{
+    let app = get_service().await;
+
+    let req = test::TestRequest::patch()
+        .set_json(&params)
+        .uri(route)
+        .cookie(cookie.clone().into_owned());
+
+    let res = test::call_service(&app, req.to_request()).await;
+
+    read_response_json(res).await
+}
+
+pub async fn test_put_as<T: Serialize, R>(route: &str, params: T, cookie: &Cookie<'_>) -> (u16, R)
+where
+    R: DeserializeOwned,
+{
+    let app = get_service().await;
+
+    let req = test::TestRequest::put()
+        .set_json(&params)
+        .uri(route)
+        .cookie(cookie.clone().into_owned());
+
+    let res = test::call_service(&app, req.to_request()).await;
+
+    read_response_json(res).await
+}
+
+pub async fn test_delete_as<R>(route: &str, cookie: &Cookie<'_>) -> (u16, R)
+where
+    R: DeserializeOwned,
+{
+    let app = get_service().await;
+    let req = test::TestRequest::delete()
+        .uri(route)
+        .cookie(cookie.clone().into_owned());
+    let res = test::call_service(&app, req.to_request()).await;
+
+    read_response_json(res).await
+}
+
+/// Creates a user holding `role` in the event, then signs them in. The username is
+/// `{role}-{event_id}`, and the password is always "correct horse".
+pub async fn sign_in_as(event_id: i32, role: Role) -> Cookie<'static> {
+    let conn = get_conn(&db::new_pool()).unwrap();
+
+    let username = format!("{}-{}", role.as_str(), event_id);
+    let user = User::create(&conn, &username, "correct horse").unwrap();
+    EventMember::set_role(&conn, event_id, user.id, role).unwrap();
+
+    sign_in(&username, "correct horse").await
+}
+
+/// A `token` for a new user with no role in any event, like `routes::auth::ws_token` hands out
+pub fn ws_token() -> String {
+    let conn = get_conn(&db::new_pool()).unwrap();
+
+    let username = format!("participant-{}", Uuid::new_v4());
+    let user = User::create(&conn, &username, "correct horse").unwrap();
+
+    token_for(user.id)
+}
+
+/// A `token` for a user that already exists
+pub fn token_for(user_id: i32) -> String {
+    TokenKey::new(SECRET_KEY).sign(
+        CurrentUser { id: user_id },
+        Utc::now() + Duration::seconds(TOKEN_LIFETIME_SECONDS),
+    )
+}
+
+/// The websocket url for an event on `srv`, opened as a new participant
+pub fn websocket_url(srv: &actix_test::TestServer, event_id: i32) -> String {
+    srv.url(&format!("/ws/?event_id={}&token={}", event_id, ws_token()))
 }
 
 pub fn get_websocket_frame_data(frame: ws::Frame) -> Option<MessageToClient> {
@@ -88,3 +248,30 @@ pub fn get_websocket_frame_data(frame: ws::Frame) -> Option<MessageToClient> {
 
     None
 }
+
+/// The reply to a command, or `None` when the frame is a broadcast instead, so tests can
+/// tell them apart when both arrive on the same socket
+pub fn get_websocket_reply(frame: &ws::Frame) -> Option<CommandReply> {
+    match frame {
+        ws::Frame::Text(text) => serde_json::from_slice(text).ok(),
+        _ => None,
+    }
+}
+
+/// The next frame from a websocket, skipping presence since it arrives on its own schedule
+pub async fn next_websocket_frame<S>(stream: &mut S) -> ws::Frame
+where
+    S: Stream<Item = Result<ws::Frame, ws::ProtocolError>> + Unpin,
+{
+    loop {
+        let frame = stream.next().await.unwrap().unwrap();
+        if let ws::Frame::Text(text) = &frame {
+            let value: Value = serde_json::from_slice(text).unwrap();
+            if value["msg_type"] == "presence" {
+                continue;
+            }
+        }
+
+        return frame;
+    }
+}
@@ -0,0 +1,227 @@
+use actix::Addr;
+use actix_web::{web::block, ResponseError};
+use serde::{Deserialize, Serialize};
+use serde_json::{to_value, Value};
+
+use db::{
+    get_conn,
+    models::{Question, Vote},
+    PgPool,
+};
+use errors::{Error, ErrorResponse, Validator};
+
+use crate::auth::CurrentUser;
+
+use super::join_event;
+use super::server::{Join, MessageToClient, Server, ServerEvent, PROTOCOL_VERSION};
+
+/// A command sent by a client over its websocket, e.g.
+/// `{"id": "1", "command": "vote", "question_id": 4}`.
+/// `id` is picked by the client and comes back on the reply
+#[derive(Deserialize, Serialize)]
+pub struct CommandRequest {
+    pub id: String,
+    #[serde(flatten)]
+    pub command: Command,
+}
+
+/// Commands act on the event the socket was opened for, which the handshake checked. Fields
+/// a command doesn't take are an error, rather than the command quietly acting on that event
+#[derive(Deserialize, Serialize)]
+#[serde(tag = "command", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Command {
+    SubmitQuestion {
+        body: String,
+    },
+    /// Votes as the user the socket was opened by
+    Vote {
+        question_id: i32,
+    },
+    /// Also receive the messages of another event the user is a member of. Commands still
+    /// act on the socket's own event
+    Subscribe {
+        event_id: i32,
+    },
+}
+
+impl CommandRequest {
+    /// The request id is read on its own first, so a command we can't make sense of
+    /// can still be answered when the id itself is fine
+    pub fn parse(text: &str) -> Result<CommandRequest, (Option<String>, Error)> {
+        let value: Value = serde_json::from_str(text)
+            .map_err(|err| (None, Error::bad_request("invalid_json", err.to_string())))?;
+        let id = value
+            .get("id")
+            .and_then(Value::as_str)
+            .map(|id| id.to_string());
+
+        serde_json::from_value(value)
+            .map_err(|err| (id, Error::bad_request("invalid_command", err.to_string())))
+    }
+}
+
+/// Sent back to the client for each command
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CommandReply {
+    pub version: u32,
+    pub request_id: Option<String>,
+    #[serde(flatten)]
+    pub event: ReplyEvent,
+}
+
+/// Tagged the same way as `ServerEvent`, so replies are `{"msg_type": "ack", "data": {...}}`
+/// and `{"msg_type": "error", "data": {...}}`
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "msg_type", content = "data", rename_all = "lowercase")]
+pub enum ReplyEvent {
+    /// Whatever the matching HTTP route would return
+    Ack(Value),
+    Error(CommandError),
+}
+
+/// The `ErrorResponse` the matching HTTP route would send, along with its status code
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CommandError {
+    pub status: u16,
+    #[serde(flatten)]
+    pub body: ErrorResponse,
+}
+
+impl CommandReply {
+    pub fn ack(request_id: String, data: Value) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            request_id: Some(request_id),
+            event: ReplyEvent::Ack(data),
+        }
+    }
+
+    pub fn error(request_id: Option<String>, error: &Error) -> Self {
+        error.log();
+
+        Self {
+            version: PROTOCOL_VERSION,
+            request_id,
+            event: ReplyEvent::Error(CommandError {
+                status: error.status_code().as_u16(),
+                body: error.into(),
+            }),
+        }
+    }
+}
+
+/// Everything a command needs from the session that received it
+pub struct CommandContext {
+    pub pool: PgPool,
+    pub server_addr: Addr<Server>,
+    pub session_id: String,
+    pub user: CurrentUser,
+    pub event_id: i32,
+}
+
+impl CommandContext {
+    pub async fn execute(self, command: Command) -> Result<Value, Error> {
+        match command {
+            Command::SubmitQuestion { body } => self.submit_question(body).await,
+            Command::Vote { question_id } => self.vote(question_id).await,
+            Command::Subscribe { event_id } => self.subscribe(event_id).await,
+        }
+    }
+
+    // Same as `routes::questions::create`
+    async fn submit_question(&self, body: String) -> Result<Value, Error> {
+        Validator::new()
+            .required("body", &body, "Body is required")
+            .finish()?;
+
+        let connection = get_conn(&self.pool)?;
+        let event_id = self.event_id;
+        let author_id = Some(self.user.id);
+        let res = block(move || Question::create(&connection, event_id, author_id, &body)).await?;
+        let question = res?;
+
+        let msg = MessageToClient::new(event_id, ServerEvent::NewQuestion(question.clone()));
+        self.server_addr.do_send(msg);
+
+        to_value(&question).map_err(|err| Error::InternalServerError(err.to_string()))
+    }
+
+    // Same as `routes::votes::create`
+    async fn vote(&self, question_id: i32) -> Result<Value, Error> {
+        let connection = get_conn(&self.pool)?;
+        let event_id = self.event_id;
+        let user_id = self.user.id;
+        let res = block(move || Vote::create(&connection, event_id, question_id, user_id)).await?;
+        let question = res?;
+
+        let msg = MessageToClient::new(event_id, ServerEvent::VoteChanged(question.clone()));
+        self.server_addr.do_send(msg);
+
+        to_value(&question).map_err(|err| Error::InternalServerError(err.to_string()))
+    }
+
+    // Subscribing to another event joins it the same way opening a socket on it would, with
+    // the user's role there rather than the session's
+    async fn subscribe(&self, event_id: i32) -> Result<Value, Error> {
+        let connection = get_conn(&self.pool)?;
+        let user = self.user;
+        let res = block(move || join_event(&connection, event_id, user)).await?;
+        let (event, role) = res?;
+
+        self.server_addr.do_send(Join {
+            id: self.session_id.clone(),
+            event_id,
+            role,
+        });
+
+        to_value(&event).map_err(|err| Error::InternalServerError(err.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use errors::Error;
+
+    use super::{Command, CommandRequest};
+
+    fn code(error: Error) -> String {
+        match error {
+            Error::BadRequest(details) => details[0].code.clone(),
+            error => panic!("expected a bad request, got {:?}", error),
+        }
+    }
+
+    #[test]
+    fn test_parse_command() {
+        let request =
+            CommandRequest::parse(r#"{"id": "1", "command": "vote", "question_id": 4}"#).unwrap();
+        assert_eq!(request.id, "1");
+        match request.command {
+            Command::Vote { question_id } => assert_eq!(question_id, 4),
+            _ => panic!("expected a vote"),
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let (id, error) = CommandRequest::parse("not json").err().unwrap();
+        assert_eq!(id, None);
+        assert_eq!(code(error), "invalid_json");
+
+        // the id is still known when the command isn't
+        let (id, error) = CommandRequest::parse(r#"{"id": "2", "command": "shout"}"#)
+            .err()
+            .unwrap();
+        assert_eq!(id.as_deref(), Some("2"));
+        assert_eq!(code(error), "invalid_command");
+
+        // commands can't be pointed at another event
+        let (id, error) = CommandRequest::parse(
+            r#"{"id": "3", "command": "vote", "event_id": 2, "question_id": 4}"#,
+        )
+        .err()
+        .unwrap();
+        assert_eq!(id.as_deref(), Some("3"));
+        assert_eq!(code(error), "invalid_command");
+    }
+}
@@ -0,0 +1,180 @@
+use std::sync::mpsc::{channel, Sender};
+use std::thread;
+use std::time::Duration;
+
+use actix::{
+    prelude::{Addr, Message as ActixMessage},
+    WeakAddr,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{from_str, to_string};
+
+use db::{get_conn, models::EventMessage, PgPool};
+use errors::Error;
+
+use super::server::{MessageToClient, Server, REPLAY_BUFFER_SIZE};
+
+/// Every instance publishes its broadcasts on, and listens to, this channel
+pub const NOTIFY_CHANNEL: &str = "event_messages";
+
+/// Every instance announces its participants on, and listens to, this channel
+pub const PRESENCE_CHANNEL: &str = "event_presence";
+
+/// How long the listener waits before reconnecting after losing its connection
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// A broadcast from any instance, this one included, as saved by `Publisher`
+#[derive(ActixMessage)]
+#[rtype(result = "()")]
+pub struct Published(pub EventMessage);
+
+/// How many participants one instance has in an event, announced by that instance whenever
+/// it changes, and on every heartbeat, so every instance can add them up. Each instance sends
+/// its own count rather than each join and leave, so one that is missed is put right by the
+/// next
+#[derive(ActixMessage, Debug, Deserialize, Serialize)]
+#[rtype(result = "()")]
+pub struct InstancePresence {
+    pub instance: String,
+    pub event_id: i32,
+    pub participants: usize,
+}
+
+enum Job {
+    Broadcast(MessageToClient),
+    Presence(InstancePresence),
+}
+
+/// Saves broadcasts under the next seq, see `EventMessage::publish`. They are published one
+/// at a time off the actor, in the order they were sent. With `fan_out` they are announced to
+/// every instance, which hear about them through `listen`, otherwise they go straight back to
+/// `addr` as `Published`. Either way nothing is sent until it is saved, so a restart can't
+/// hand a seq out again. Presence is announced through it too, so an instance's counts
+/// arrive in order
+pub struct Publisher {
+    sender: Sender<Job>,
+}
+
+impl Publisher {
+    pub fn start(pool: PgPool, addr: WeakAddr<Server>, fan_out: bool) -> Self {
+        let (sender, receiver) = channel::<Job>();
+        let channel = if fan_out { Some(NOTIFY_CHANNEL) } else { None };
+
+        // ends along with the server, once the sender is dropped
+        thread::spawn(move || {
+            for job in receiver {
+                match job {
+                    Job::Broadcast(msg) => match publish(&pool, channel, msg) {
+                        Ok(published) => {
+                            if let (None, Some(addr)) = (channel, addr.upgrade()) {
+                                addr.do_send(Published(published));
+                            }
+                        }
+                        Err(err) => error!("Could not publish broadcast: {:?}", err),
+                    },
+                    Job::Presence(presence) => {
+                        if let Err(err) = announce(&pool, &presence) {
+                            error!("Could not announce presence: {:?}", err);
+                        }
+                    }
+                }
+            }
+        });
+
+        Self { sender }
+    }
+
+    pub fn publish(&self, msg: MessageToClient) {
+        if self.sender.send(Job::Broadcast(msg)).is_err() {
+            error!("Could not publish broadcast, the publisher has stopped");
+        }
+    }
+
+    pub fn announce(&self, presence: InstancePresence) {
+        if self.sender.send(Job::Presence(presence)).is_err() {
+            error!("Could not announce presence, the publisher has stopped");
+        }
+    }
+}
+
+fn publish(
+    pool: &PgPool,
+    channel: Option<&str>,
+    msg: MessageToClient,
+) -> Result<EventMessage, Error> {
+    let conn = get_conn(pool)?;
+    EventMessage::publish(
+        &conn,
+        channel,
+        msg.event_id,
+        msg.event.moderators_only(),
+        REPLAY_BUFFER_SIZE as i64,
+        |seq| {
+            to_string(&MessageToClient {
+                seq: Some(seq),
+                ..msg
+            })
+            .map_err(|err| Error::InternalServerError(err.to_string()))
+        },
+    )
+}
+
+fn announce(pool: &PgPool, presence: &InstancePresence) -> Result<(), Error> {
+    let conn = get_conn(pool)?;
+    let payload = to_string(presence).map_err(|err| Error::InternalServerError(err.to_string()))?;
+
+    db::notify(&conn, PRESENCE_CHANNEL, &payload)
+}
+
+/// Feeds everything published after `last_seq` into `addr` as `Published`, along with every
+/// instance's `InstancePresence`, for as long as the server runs. Broadcast notifications only
+/// say that something was published, the messages themselves are loaded from
+/// `event_messages`, so nothing is missed while reconnecting
+pub fn listen(addr: Addr<Server>, pool: PgPool, mut last_seq: i64) {
+    thread::spawn(move || loop {
+        let res = db::listen(&[NOTIFY_CHANNEL, PRESENCE_CHANNEL], |heard| {
+            let heard = match heard {
+                Some(heard) => heard,
+                None => return addr.connected(),
+            };
+
+            // nothing heard means it has only just started listening, and may have missed some
+            if heard.is_empty() || heard.iter().any(|n| n.channel == NOTIFY_CHANNEL) {
+                if let Err(err) = catch_up(&addr, &pool, &mut last_seq) {
+                    error!("Could not load published broadcasts: {:?}", err);
+                }
+            }
+            for notification in heard.iter().filter(|n| n.channel == PRESENCE_CHANNEL) {
+                match from_str::<InstancePresence>(&notification.payload) {
+                    Ok(presence) => addr.do_send(presence),
+                    Err(err) => error!("Could not read announced presence: {:?}", err),
+                }
+            }
+            addr.connected()
+        });
+
+        match res {
+            Ok(()) => return,
+            Err(err) => error!("Stopped listening for broadcasts: {:?}", err),
+        }
+        if !addr.connected() {
+            return;
+        }
+        thread::sleep(RECONNECT_DELAY);
+    });
+}
+
+fn catch_up(addr: &Addr<Server>, pool: &PgPool, last_seq: &mut i64) -> Result<(), Error> {
+    let conn = get_conn(pool)?;
+    loop {
+        let messages = EventMessage::after(&conn, *last_seq, REPLAY_BUFFER_SIZE as i64)?;
+        if messages.is_empty() {
+            return Ok(());
+        }
+
+        for message in messages {
+            *last_seq = message.seq;
+            addr.do_send(Published(message));
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use std::env;
 use std::time::{Duration, Instant};
 
 use uuid::Uuid;
@@ -5,40 +6,110 @@ use uuid::Uuid;
 use actix::{
     fut,
     prelude::{Actor, Addr, Handler, StreamHandler},
-    ActorContext, ActorFutureExt, AsyncContext, ContextFutureSpawner, WrapFuture,
+    ActorContext, ActorFutureExt, AsyncContext, ContextFutureSpawner, Running, WrapFuture,
+};
+use actix_web::{
+    http::header::ORIGIN,
+    web::{self, block, Data, Query},
+    HttpRequest, HttpResponse,
 };
-use actix_web::{web, HttpRequest, HttpResponse};
 use actix_web_actors::ws;
+use diesel::PgConnection;
+use serde::{Deserialize, Serialize};
+use serde_json::to_string;
 
+use db::{
+    get_conn,
+    models::{Event, EventMember, Role},
+    PgPool,
+};
 use errors::Error;
 
+use crate::auth::{CurrentUser, TokenKey};
+
+mod commands;
+mod fanout;
 mod server;
+mod sse;
+pub use self::commands::*;
 pub use self::server::*;
+pub use self::sse::*;
 
 const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
 const CLIENT_TIMEOUT: Duration = Duration::from_secs(30);
 
 pub struct WebSocketSession {
     id: String,
+    event_id: i32,
+    // who opened the socket, and their role in `event_id`
+    user: CurrentUser,
+    role: Role,
     hb: Instant,
+    // set when the client is reconnecting, see `Connect`
+    last_seq: Option<i64>,
+    pool: PgPool,
     server_addr: Addr<Server>,
 }
 
 impl WebSocketSession {
-    fn new(server_addr: Addr<Server>) -> Self {
+    fn new(
+        pool: PgPool,
+        server_addr: Addr<Server>,
+        event_id: i32,
+        user: CurrentUser,
+        role: Role,
+        last_seq: Option<i64>,
+    ) -> Self {
         Self {
             id: Uuid::new_v4().to_string(),
+            event_id,
+            user,
+            role,
             hb: Instant::now(),
+            last_seq,
+            pool,
             server_addr,
         }
     }
 
+    /// Runs a client command, see `Command`. Replies go out as they finish, which isn't
+    /// necessarily the order the commands came in
+    fn handle_command(&self, text: &str, ctx: &mut <Self as Actor>::Context) {
+        let request = match CommandRequest::parse(text) {
+            Ok(request) => request,
+            Err((request_id, error)) => {
+                send_reply(CommandReply::error(request_id, &error), ctx);
+                return;
+            }
+        };
+
+        let commands = CommandContext {
+            pool: self.pool.clone(),
+            server_addr: self.server_addr.clone(),
+            session_id: self.id.clone(),
+            user: self.user,
+            event_id: self.event_id,
+        };
+        let request_id = request.id;
+
+        commands
+            .execute(request.command)
+            .into_actor(self)
+            .map(move |res, _act, ctx| {
+                let reply = match res {
+                    Ok(data) => CommandReply::ack(request_id, data),
+                    Err(error) => CommandReply::error(Some(request_id), &error),
+                };
+                send_reply(reply, ctx);
+            })
+            .spawn(ctx);
+    }
+
     fn send_heartbeat(&self, ctx: &mut <Self as Actor>::Context) {
         ctx.run_interval(HEARTBEAT_INTERVAL, |act, ctx| {
             if Instant::now().duration_since(act.hb) > CLIENT_TIMEOUT {
                 info!("Websocket Client heartbeat failed, disconnecting!");
-                act.server_addr.do_send(Disconnect { id: act.id.clone() });
-                // stop actor
+                // stop actor, which disconnects it from the server
                 ctx.stop();
 
                 // don't try to send a ping
@@ -58,26 +129,50 @@ impl Actor for WebSocketSession {
         let session_addr = ctx.address();
         self.server_addr
             .send(Connect {
-                addr: session_addr.recipient(),
+                addr: session_addr.clone().recipient(),
+                evict: session_addr.recipient(),
                 id: self.id.clone(),
+                event_id: self.event_id,
+                role: self.role,
+                last_seq: self.last_seq,
             })
             .into_actor(self)
             .then(|res, _act, ctx| {
                 match res {
-                    Ok(_res) => {}
+                    Ok(queue_depth) => ctx.set_mailbox_capacity(queue_depth),
                     _ => ctx.stop(),
                 }
                 fut::ready(())
             })
             .wait(ctx);
     }
+
+    // However the session ends, including the client just going away
+    fn stopping(&mut self, _: &mut Self::Context) -> Running {
+        self.server_addr.do_send(Disconnect {
+            id: self.id.clone(),
+        });
+        Running::Stop
+    }
 }
 
 impl Handler<Message> for WebSocketSession {
     type Result = ();
 
     fn handle(&mut self, msg: Message, ctx: &mut Self::Context) {
-        ctx.text(msg.0);
+        ctx.text(msg.text);
+    }
+}
+
+impl Handler<Evict> for WebSocketSession {
+    type Result = ();
+
+    fn handle(&mut self, _: Evict, ctx: &mut Self::Context) {
+        ctx.close(Some(ws::CloseReason {
+            code: ws::CloseCode::Again,
+            description: Some("Too far behind, reconnect with last_seq".to_string()),
+        }));
+        ctx.stop();
     }
 }
 
@@ -91,12 +186,10 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WebSocketSession
             Ok(ws::Message::Pong(_)) => {
                 self.hb = Instant::now();
             }
+            Ok(ws::Message::Text(text)) => self.handle_command(&text, ctx),
             Ok(ws::Message::Binary(bin)) => ctx.binary(bin),
             Ok(ws::Message::Close(reason)) => {
                 info!("closed ws session");
-                self.server_addr.do_send(Disconnect {
-                    id: self.id.clone(),
-                });
                 ctx.close(reason);
                 ctx.stop();
             }
@@ -109,16 +202,596 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WebSocketSession
     }
 }
 
+fn send_reply(reply: CommandReply, ctx: &mut <WebSocketSession as Actor>::Context) {
+    match to_string(&reply) {
+        Ok(text) => ctx.text(text),
+        Err(err) => error!("Command reply did not convert to string {:?}", err),
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct WsParams {
+    event_id: Option<i32>,
+    /// The `seq` of the last message seen, when reconnecting
+    last_seq: Option<i64>,
+    /// From `routes::auth::ws_token`, for clients that can't send the session cookie
+    token: Option<String>,
+}
+
+/// The event a socket is opened on or subscribed to, and the user's role in it. Anyone
+/// without a membership takes part as a participant, and an event that doesn't exist is a 404
+fn join_event(
+    conn: &PgConnection,
+    event_id: i32,
+    user: CurrentUser,
+) -> Result<(Event, Role), Error> {
+    let event = Event::find(conn, event_id)?;
+    let role = EventMember::role(conn, event_id, user.id)?.unwrap_or(Role::Participant);
+
+    Ok((event, role))
+}
+
+/// The user opening a websocket or stream, from `token` when there is one and the session
+/// cookie otherwise, along with their role in the event. Nobody signed in is a 401, and an
+/// event that doesn't exist a 404
+async fn authenticate(
+    pool: &PgPool,
+    key: &TokenKey,
+    user: Option<CurrentUser>,
+    token: Option<&str>,
+    event_id: i32,
+) -> Result<(CurrentUser, Role), Error> {
+    let user = match token {
+        Some(token) => key.verify(token)?,
+        None => user.ok_or(Error::Unauthorized)?,
+    };
+
+    let connection = get_conn(pool)?;
+    let res = block(move || join_event(&connection, event_id, user)).await?;
+    let (_, role) = res?;
+
+    Ok((user, role))
+}
+
+/// Browsers send the session cookie with a websocket handshake from any page, and unlike with
+/// `fetch`, CORS doesn't stop that page from using the socket. So a handshake that signs in
+/// with the cookie has to come from `CLIENT_HOST`. Clients that aren't browsers don't send an
+/// `Origin` at all, and tokens have to be asked for with the cookie, so neither is checked
+fn check_origin(req: &HttpRequest) -> Result<(), Error> {
+    let origin = match req.headers().get(ORIGIN) {
+        Some(origin) => origin,
+        None => return Ok(()),
+    };
+
+    match env::var("CLIENT_HOST") {
+        Ok(client_host) if origin.as_bytes() == client_host.as_bytes() => Ok(()),
+        _ => Err(Error::Forbidden),
+    }
+}
+
 pub async fn ws_index(
     req: HttpRequest,
     stream: web::Payload,
-    server_addr: web::Data<Addr<Server>>,
+    pool: Data<PgPool>,
+    server_addr: Data<Addr<Server>>,
+    key: Data<TokenKey>,
+    user: Option<CurrentUser>,
+    params: Query<WsParams>,
 ) -> Result<HttpResponse, Error> {
+    let event_id = params
+        .event_id
+        .ok_or_else(|| Error::invalid_field("event_id", "required", "event_id is required"))?;
+
+    if params.token.is_none() {
+        check_origin(&req)?;
+    }
+    let (user, role) = authenticate(&pool, &key, user, params.token.as_deref(), event_id).await?;
+
     let res = ws::start(
-        WebSocketSession::new(server_addr.get_ref().clone()),
+        WebSocketSession::new(
+            pool.get_ref().clone(),
+            server_addr.get_ref().clone(),
+            event_id,
+            user,
+            role,
+            params.last_seq,
+        ),
         &req,
         stream,
     )?;
 
     Ok(res)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::time::Duration;
+
+    use actix_web_actors::ws;
+    use awc::{error::WsClientError, Client};
+    use chrono::Utc;
+    use diesel::{self, ExpressionMethods, QueryDsl, RunQueryDsl};
+    use futures::SinkExt;
+    use serde_json::{self, json, Value};
+
+    use db::{
+        get_conn,
+        models::{Event, EventMember, NewQuestion, Question, QuestionStatus, Role, User, Vote},
+        new_pool,
+        schema::{event_messages, events, questions, users, votes},
+    };
+    use errors::ErrorResponse;
+
+    use super::{ReplyEvent, ServerEvent, PROTOCOL_VERSION};
+    use crate::auth::{CurrentUser, TokenKey};
+    use crate::tests;
+
+    fn command(value: Value) -> ws::Message {
+        ws::Message::Text(value.to_string().into())
+    }
+
+    #[actix_rt::test]
+    async fn test_messages_only_reach_sessions_in_the_event() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+        let other_event = Event::create(&conn, "Another event", false).unwrap();
+
+        let srv = tests::get_test_server();
+
+        let client = Client::default();
+        let ws_conn = client
+            .ws(tests::websocket_url(&srv, event.id))
+            .connect()
+            .await
+            .unwrap();
+
+        for (event_id, body) in &[(other_event.id, "Elsewhere"), (event.id, "Over here")] {
+            let res = srv
+                .post(format!("/api/events/{}/questions", event_id))
+                .send_json(&json!({ "body": body }))
+                .await
+                .unwrap();
+            assert_eq!(res.status().as_u16(), 200);
+        }
+
+        let mut stream = ws_conn.1;
+        let frame = tests::next_websocket_frame(&mut stream).await;
+
+        let data = tests::get_websocket_frame_data(frame);
+        let msg = data.expect("Message was not a string");
+        assert_eq!(msg.event_id, event.id);
+        let question = match msg.event {
+            ServerEvent::NewQuestion(question) => question,
+            event => panic!("expected newquestion, got {:?}", event),
+        };
+        assert_eq!(question.body, "Over here");
+
+        drop(stream);
+
+        srv.stop().await;
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_ws_requires_an_event() {
+        let res: (u16, ErrorResponse) = tests::test_get("/ws/").await;
+        assert_eq!(res.0, 400);
+        assert_eq!(res.1.errors, vec!["event_id is required"]);
+
+        let route = format!("/ws/?event_id=0&token={}", tests::ws_token());
+        let res: (u16, ErrorResponse) = tests::test_get(&route).await;
+        assert_eq!(res.0, 404);
+        assert_eq!(res.1.errors, vec!["Record not found"]);
+    }
+
+    #[actix_rt::test]
+    async fn test_ws_requires_a_user() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+
+        let route = format!("/ws/?event_id={}", event.id);
+        let res: (u16, ErrorResponse) = tests::test_get(&route).await;
+        assert_eq!(res.0, 401);
+
+        let key = TokenKey::new(tests::SECRET_KEY);
+        let expired = key.sign(
+            CurrentUser { id: 1 },
+            Utc::now() - chrono::Duration::seconds(1),
+        );
+        let forged = TokenKey::new(b"some other key").sign(CurrentUser { id: 1 }, Utc::now());
+        for token in &[expired, forged, "nonsense".to_string()] {
+            let route = format!("/ws/?event_id={}&token={}", event.id, token);
+            let res: (u16, ErrorResponse) = tests::test_get(&route).await;
+            assert_eq!(res.0, 401, "token {} was let in", token);
+        }
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+    }
+
+    // Puts `CLIENT_HOST` back how it was when dropped, however the test using it ends, so
+    // the value doesn't leak into other tests in the process
+    struct ClientHost(Option<String>);
+
+    impl ClientHost {
+        fn set(value: &str) -> Self {
+            let previous = env::var("CLIENT_HOST").ok();
+            env::set_var("CLIENT_HOST", value);
+            ClientHost(previous)
+        }
+    }
+
+    impl Drop for ClientHost {
+        fn drop(&mut self) {
+            match &self.0 {
+                Some(previous) => env::set_var("CLIENT_HOST", previous),
+                None => env::remove_var("CLIENT_HOST"),
+            }
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_ws_cookie_needs_the_client_origin() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+
+        let cookie = tests::sign_in_as(event.id, Role::Participant).await;
+        let _client_host = ClientHost::set("http://localhost:3000");
+
+        let srv = tests::get_test_server();
+        let client = Client::default();
+        let route = format!("/ws/?event_id={}", event.id);
+
+        // another site's page, signed in as whoever is using the browser
+        let res = client
+            .ws(srv.url(&route))
+            .origin("https://elsewhere.example")
+            .cookie(cookie.clone())
+            .connect()
+            .await;
+        match res {
+            Err(WsClientError::InvalidResponseStatus(status)) => assert_eq!(status.as_u16(), 403),
+            _ => panic!("the handshake was let through"),
+        }
+
+        // a token isn't sent along by the browser, so it is fine from anywhere
+        let route_with_token = format!("{}&token={}", route, tests::ws_token());
+        let res = client
+            .ws(srv.url(&route_with_token))
+            .origin("https://elsewhere.example")
+            .connect()
+            .await;
+        assert!(res.is_ok());
+
+        for origin in &[Some("http://localhost:3000"), None] {
+            let mut req = client.ws(srv.url(&route)).cookie(cookie.clone());
+            if let Some(origin) = origin {
+                req = req.origin(*origin);
+            }
+            assert!(req.connect().await.is_ok(), "{:?} was turned away", origin);
+        }
+
+        srv.stop().await;
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_submit_question_command() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+
+        let srv = tests::get_test_server();
+
+        let client = Client::default();
+        let (_, mut ws_conn) = client
+            .ws(tests::websocket_url(&srv, event.id))
+            .connect()
+            .await
+            .unwrap();
+
+        ws_conn
+            .send(command(json!({
+                "id": "q-1",
+                "command": "submit_question",
+                "body": "Over the socket",
+            })))
+            .await
+            .unwrap();
+
+        // the ack and the broadcast to the room can arrive in either order
+        let mut reply = None;
+        let mut broadcast = None;
+        for _ in 0..2 {
+            let frame = tests::next_websocket_frame(&mut ws_conn).await;
+            match tests::get_websocket_reply(&frame) {
+                Some(ack) => reply = Some(ack),
+                None => broadcast = tests::get_websocket_frame_data(frame),
+            }
+        }
+
+        let reply = reply.expect("no reply to the command");
+        assert_eq!(reply.request_id.as_deref(), Some("q-1"));
+        let question: Question = match reply.event {
+            ReplyEvent::Ack(data) => serde_json::from_value(data).unwrap(),
+            event => panic!("expected ack, got {:?}", event),
+        };
+        assert_eq!(question.body, "Over the socket");
+        assert_eq!(question.event_id, event.id);
+
+        // broadcasts keep their wire names, and both carry the protocol version
+        let broadcast = broadcast.expect("no broadcast of the question");
+        assert!(matches!(broadcast.event, ServerEvent::NewQuestion(_)));
+        assert_eq!(reply.version, PROTOCOL_VERSION);
+        assert_eq!(broadcast.version, PROTOCOL_VERSION);
+
+        srv.stop().await;
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_command_errors() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+        let question = diesel::insert_into(questions::table)
+            .values(NewQuestion {
+                body: "A question".to_string(),
+                event_id: event.id,
+                status: QuestionStatus::Open,
+                author_id: None,
+            })
+            .get_result::<Question>(&conn)
+            .unwrap();
+
+        let srv = tests::get_test_server();
+
+        let client = Client::default();
+        let (_, mut ws_conn) = client
+            .ws(tests::websocket_url(&srv, event.id))
+            .connect()
+            .await
+            .unwrap();
+
+        let cases = vec![
+            (
+                json!({ "id": "1", "command": "shout" }),
+                400,
+                "invalid_command",
+            ),
+            (
+                json!({ "id": "2", "command": "submit_question", "body": "" }),
+                400,
+                "required",
+            ),
+            (
+                json!({ "id": "3", "command": "vote", "question_id": 0 }),
+                404,
+                "not_found",
+            ),
+            (
+                json!({ "id": "4", "command": "subscribe", "event_id": 0 }),
+                404,
+                "not_found",
+            ),
+        ];
+
+        for (body, status, code) in cases {
+            ws_conn.send(command(body.clone())).await.unwrap();
+
+            let frame = tests::next_websocket_frame(&mut ws_conn).await;
+            let reply = tests::get_websocket_reply(&frame).unwrap();
+            assert_eq!(reply.request_id.as_deref(), body["id"].as_str());
+
+            let error = match reply.event {
+                ReplyEvent::Error(error) => error,
+                event => panic!("expected error, got {:?}", event),
+            };
+            assert_eq!(error.status, status);
+            assert_eq!(error.body.details[0].code, code);
+        }
+
+        // the socket is still open after errors
+        ws_conn
+            .send(command(json!({
+                "id": "5",
+                "command": "vote",
+                "question_id": question.id,
+            })))
+            .await
+            .unwrap();
+        let mut acked = false;
+        for _ in 0..2 {
+            let frame = tests::next_websocket_frame(&mut ws_conn).await;
+            let reply = tests::get_websocket_reply(&frame);
+            acked |= matches!(reply.map(|reply| reply.event), Some(ReplyEvent::Ack(_)));
+        }
+        assert!(acked);
+
+        // the vote is cast as the user the socket was opened by
+        let votes = votes::dsl::votes.load::<Vote>(&conn).unwrap();
+        assert_eq!(votes.len(), 1);
+        let voter = User::find(&conn, votes[0].user_id).unwrap();
+        assert!(voter.username.starts_with("participant-"));
+
+        srv.stop().await;
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_subscribe_command() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+        let other_event = Event::create(&conn, "Another event", false).unwrap();
+        let unjoined_event = Event::create(&conn, "A moderated one", true).unwrap();
+
+        let user = User::create(&conn, "subscriber", "correct horse").unwrap();
+        EventMember::set_role(&conn, other_event.id, user.id, Role::Participant).unwrap();
+
+        let srv = tests::get_test_server();
+
+        let client = Client::default();
+        let url = format!(
+            "/ws/?event_id={}&token={}",
+            event.id,
+            tests::token_for(user.id)
+        );
+        let (_, mut ws_conn) = client.ws(srv.url(&url)).connect().await.unwrap();
+
+        // as with opening a socket, events that don't exist can't be subscribed to
+        ws_conn
+            .send(command(json!({
+                "id": "no",
+                "command": "subscribe",
+                "event_id": 0,
+            })))
+            .await
+            .unwrap();
+        let frame = tests::next_websocket_frame(&mut ws_conn).await;
+        let reply = tests::get_websocket_reply(&frame).unwrap();
+        let error = match reply.event {
+            ReplyEvent::Error(error) => error,
+            event => panic!("expected error, got {:?}", event),
+        };
+        assert_eq!(error.status, 404);
+
+        // and ones the user has no role in are joined as a participant
+        for subscribe_to in &[unjoined_event.id, other_event.id] {
+            ws_conn
+                .send(command(json!({
+                    "id": "sub",
+                    "command": "subscribe",
+                    "event_id": subscribe_to,
+                })))
+                .await
+                .unwrap();
+            let frame = tests::next_websocket_frame(&mut ws_conn).await;
+            let reply = tests::get_websocket_reply(&frame).unwrap();
+            let subscribed: Event = match reply.event {
+                ReplyEvent::Ack(data) => serde_json::from_value(data).unwrap(),
+                event => panic!("expected ack, got {:?}", event),
+            };
+            assert_eq!(subscribed.id, *subscribe_to);
+        }
+
+        // so a question waiting on a moderator there doesn't reach the socket
+        let res = srv
+            .post(format!("/api/events/{}/questions", unjoined_event.id))
+            .send_json(&json!({ "body": "Waiting" }))
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+
+        let res = srv
+            .post(format!("/api/events/{}/questions", other_event.id))
+            .send_json(&json!({ "body": "Elsewhere" }))
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+
+        let frame = tests::next_websocket_frame(&mut ws_conn).await;
+        let msg = tests::get_websocket_frame_data(frame).unwrap();
+        assert_eq!(msg.event_id, other_event.id);
+        assert!(matches!(msg.event, ServerEvent::NewQuestion(_)));
+
+        srv.stop().await;
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_reconnect_replays_missed_messages() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+
+        let srv = tests::get_test_server();
+        let url = |last_seq: Option<i64>| {
+            let resume = last_seq
+                .map(|last_seq| format!("&last_seq={}", last_seq))
+                .unwrap_or_default();
+            format!("{}{}", tests::websocket_url(&srv, event.id), resume)
+        };
+        let post_question = |body: &'static str| {
+            srv.post(format!("/api/events/{}/questions", event.id))
+                .send_json(&json!({ "body": body }))
+        };
+
+        let client = Client::default();
+        let (_, mut ws_conn) = client.ws(url(None)).connect().await.unwrap();
+
+        post_question("Seen").await.unwrap();
+        let frame = tests::next_websocket_frame(&mut ws_conn).await;
+        let seen = tests::get_websocket_frame_data(frame).unwrap().seq.unwrap();
+        drop(ws_conn);
+
+        post_question("Missed").await.unwrap();
+        post_question("Also missed").await.unwrap();
+
+        let (_, mut ws_conn) = client.ws(url(Some(seen))).connect().await.unwrap();
+        let mut last_seq = seen;
+        for expected in &["Missed", "Also missed"] {
+            let frame = tests::next_websocket_frame(&mut ws_conn).await;
+            let msg = tests::get_websocket_frame_data(frame).unwrap();
+            let seq = msg.seq.expect("a replayed message had no seq");
+            assert!(seq > last_seq);
+            last_seq = seq;
+
+            let question = match msg.event {
+                ServerEvent::NewQuestion(question) => question,
+                event => panic!("expected newquestion, got {:?}", event),
+            };
+            assert_eq!(question.body, *expected);
+        }
+        drop(ws_conn);
+
+        // a seq the server never handed out can't be resumed from
+        let (_, mut ws_conn) = client
+            .ws(url(Some(last_seq + 1_000)))
+            .connect()
+            .await
+            .unwrap();
+        let frame = tests::next_websocket_frame(&mut ws_conn).await;
+        let msg = tests::get_websocket_frame_data(frame).unwrap();
+        // it isn't a broadcast, so it has no seq to resume from, only the one it names
+        assert_eq!(msg.seq, None);
+        match msg.event {
+            ServerEvent::ResyncRequired {
+                last_seq: resume_from,
+                ..
+            } => assert_eq!(resume_from, last_seq),
+            event => panic!("expected resyncrequired, got {:?}", event),
+        }
+        drop(ws_conn);
+
+        // the buffer is saved in the background
+        let mut saved = Vec::new();
+        for _ in 0..20 {
+            saved = event_messages::table
+                .filter(event_messages::event_id.eq(event.id))
+                .select(event_messages::seq)
+                .order(event_messages::seq)
+                .load::<i64>(&conn)
+                .unwrap();
+            if saved.len() == 3 {
+                break;
+            }
+            actix_rt::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert_eq!(saved, vec![seen, last_seq - 1, last_seq]);
+
+        srv.stop().await;
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+    }
+}
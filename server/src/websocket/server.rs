@@ -1,75 +1,682 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::env;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
 
-use actix::prelude::{Actor, Context, Handler, Message as ActixMessage, Recipient};
+use actix::prelude::{
+    Actor, AsyncContext, Context, Handler, Message as ActixMessage, MessageResult, Recipient,
+    SendError,
+};
 use serde::{Deserialize, Serialize};
-use serde_json::{error::Result as SerdeResult, to_string, Value};
+use serde_json::to_string;
+use uuid::Uuid;
 
+use db::{
+    get_conn,
+    models::{Answer, EventMessage, Question, QuestionStatus, Role},
+    PgPool,
+};
+use errors::Error;
+
+use super::fanout::{self, InstancePresence, Published, Publisher};
+
+/// Text for a session to send on as it is, along with what it is so sessions never have to
+/// read it
+#[derive(ActixMessage, Clone)]
+#[rtype(result = "()")]
+pub struct Message {
+    pub text: String,
+    pub kind: MessageKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MessageKind {
+    /// A broadcast, kept for replays under this seq
+    Sequenced(i64),
+    /// About the connection rather than the event, so it has no seq and isn't replayed:
+    /// presence and resync notices
+    Unsequenced,
+    /// Tells the session some messages didn't fit, see `ServerEvent::MessagesDropped`
+    Dropped,
+}
+
+/// Tells a session it was dropped for falling too far behind, see `Backpressure`
 #[derive(ActixMessage)]
 #[rtype(result = "()")]
-pub struct Message(pub String);
+pub struct Evict;
+
+/// Sent on every message to clients. Bumped whenever a change to the messages would break
+/// an existing client, so clients can tell they are talking to a server they don't understand
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// How many broadcasts are kept, across all events, for clients that reconnect. Clients
+/// that missed more than this have to resync
+pub const REPLAY_BUFFER_SIZE: usize = 1000;
+
+/// Presence goes out at most this often per room, so a crowd arriving at once doesn't mean
+/// a message per person to everyone already there
+const PRESENCE_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// With `Server::fan_out`, every instance announces its participants this often even when
+/// nothing changed, so instances that started since, or missed an announcement, catch up
+const PRESENCE_HEARTBEAT: Duration = Duration::from_secs(5);
+
+/// Another instance's participants are forgotten once it hasn't announced them for this
+/// long, so an instance that crashed or was replaced doesn't keep adding to the count
+const PRESENCE_TTL: Duration = Duration::from_secs(15);
+
+/// What happens to sessions that can't keep up. A session's mailbox is its outbound queue,
+/// `queue_depth` messages deep. Broadcasts that don't fit are dropped for that session, and
+/// once more than `max_dropped` are dropped before it catches up again, by taking
+/// `queue_depth` messages in a row, it is disconnected and has to reconnect with its
+/// `last_seq`
+#[derive(Clone, Copy, Debug)]
+pub struct Backpressure {
+    pub queue_depth: usize,
+    pub max_dropped: u64,
+}
+
+impl Default for Backpressure {
+    fn default() -> Self {
+        Self {
+            queue_depth: 64,
+            max_dropped: 256,
+        }
+    }
+}
+
+impl Backpressure {
+    /// Reads `SESSION_QUEUE_DEPTH` and `SESSION_MAX_DROPPED`, using the defaults for
+    /// anything unset
+    pub fn from_env() -> Self {
+        let default = Self::default();
+
+        Self {
+            queue_depth: env_or("SESSION_QUEUE_DEPTH", default.queue_depth),
+            max_dropped: env_or("SESSION_MAX_DROPPED", default.max_dropped),
+        }
+    }
+}
+
+fn env_or<T: FromStr>(name: &str, default: T) -> T {
+    env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+/// Everything the server broadcasts to an event's room. On the wire these are
+/// `{"msg_type": "newquestion", "data": {...}}`
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "msg_type", content = "data", rename_all = "lowercase")]
+pub enum ServerEvent {
+    NewQuestion(Question),
+    UpdatedQuestion(Question),
+    DeletedQuestion(Question),
+    RestoredQuestion(Question),
+    StatusChanged(ChangedStatus),
+    VoteChanged(Question),
+    NewAnswer(PostedAnswer),
+    /// Sent to a reconnecting client instead of a replay, when the messages after its
+    /// `last_seq` are no longer kept. It should refetch over HTTP, then carry on from
+    /// `last_seq`, the last seq handed out
+    ResyncRequired {
+        oldest_seq: i64,
+        last_seq: i64,
+    },
+    Presence(Presence),
+    /// Sent ahead of the next message a session does get, after some were dropped because
+    /// its queue was full. `dropped` is how many since the last of these. The client
+    /// should refetch over HTTP, or reconnect with its `last_seq` to have them replayed
+    MessagesDropped {
+        dropped: u64,
+        total_dropped: u64,
+    },
+}
+
+/// A new answer, along with the status of the question it answers. On the wire it is the
+/// answer with a `question_status` field
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PostedAnswer {
+    #[serde(flatten)]
+    pub answer: Answer,
+    pub question_status: QuestionStatus,
+}
+
+/// A question that moved along its lifecycle, along with the status it moved from. On the
+/// wire it is the question with a `previous_status` field
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ChangedStatus {
+    #[serde(flatten)]
+    pub question: Question,
+    pub previous_status: QuestionStatus,
+}
+
+impl ServerEvent {
+    /// Questions participants can't see, see `QuestionStatus::is_visible`, are only for
+    /// moderators and hosts, and so is anything that happens to them or their answers
+    /// while they are. A status change is for everyone when the question could be seen
+    /// before or after it, so participants hear about questions leaving as well as arriving
+    pub fn moderators_only(&self) -> bool {
+        use ServerEvent::*;
+
+        let visible = match self {
+            NewQuestion(question)
+            | UpdatedQuestion(question)
+            | DeletedQuestion(question)
+            | RestoredQuestion(question)
+            | VoteChanged(question) => question.status.is_visible(),
+            StatusChanged(changed) => {
+                changed.question.status.is_visible() || changed.previous_status.is_visible()
+            }
+            NewAnswer(posted) => posted.question_status.is_visible(),
+            _ => return false,
+        };
+
+        !visible
+    }
+}
+
+/// How many websockets are open on an event, including ones that subscribed to it. With
+/// `Server::fan_out` that is across every instance
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Presence {
+    pub event_id: i32,
+    pub participants: usize,
+}
 
 #[derive(ActixMessage, Deserialize, Serialize)]
 #[rtype(result = "()")]
 pub struct MessageToClient {
-    pub msg_type: String,
-    pub data: Value,
+    pub version: u32,
+    /// Increases with every broadcast, across all events. Handed out by the database as the
+    /// broadcast is saved, see `Publisher`. `None` for events that aren't kept for replays,
+    /// see `MessageKind`, so a client never resumes from past a broadcast it didn't get
+    pub seq: Option<i64>,
+    pub event_id: i32,
+    #[serde(flatten)]
+    pub event: ServerEvent,
 }
 
 impl MessageToClient {
-    pub fn new(msg_type: &str, data: Value) -> Self {
+    pub fn new(event_id: i32, event: ServerEvent) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            seq: None,
+            event_id,
+            event,
+        }
+    }
+}
+
+/// A broadcast as it was sent, kept for replays
+struct SentMessage {
+    seq: i64,
+    event_id: i32,
+    text: String,
+    moderators_only: bool,
+}
+
+impl SentMessage {
+    fn message(&self) -> Message {
+        Message {
+            text: self.text.clone(),
+            kind: MessageKind::Sequenced(self.seq),
+        }
+    }
+}
+
+impl From<EventMessage> for SentMessage {
+    fn from(message: EventMessage) -> Self {
         Self {
-            msg_type: msg_type.to_string(),
-            data,
+            seq: message.seq,
+            event_id: message.event_id,
+            text: message.message,
+            moderators_only: message.moderators_only,
         }
     }
 }
 
+struct Session {
+    addr: Recipient<Message>,
+    evict: Recipient<Evict>,
+    // the session's role in each event it joined
+    roles: HashMap<i32, Role>,
+    // dropped since the session was last told
+    unreported: u64,
+    // dropped since the session last caught up, a notice getting through doesn't count
+    dropped: u64,
+    // delivered since the last drop
+    delivered: usize,
+    total_dropped: u64,
+}
+
+impl Session {
+    fn hears(&self, event_id: i32, moderators_only: bool) -> bool {
+        !moderators_only
+            || matches!(self.roles.get(&event_id), Some(role) if *role >= Role::Moderator)
+    }
+}
+
+/// A session's dropped messages, as counted by `Server`. `dropped` is since it last
+/// caught up, see `Backpressure`
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SessionStats {
+    pub id: String,
+    pub dropped: u64,
+    pub total_dropped: u64,
+}
+
 pub struct Server {
-    sessions: HashMap<String, Recipient<Message>>
+    sessions: HashMap<String, Session>,
+    // session ids that joined each event
+    rooms: HashMap<i32, HashSet<String>>,
+    pool: PgPool,
+    // the last seq handed out
+    last_seq: i64,
+    // the last REPLAY_BUFFER_SIZE broadcasts, oldest first
+    history: VecDeque<SentMessage>,
+    // rooms whose participant count changed since presence was last sent
+    presence_changed: HashSet<i32>,
+    presence_scheduled: bool,
+    backpressure: Backpressure,
+    fan_out: bool,
+    // tells this instance's presence apart from the others', see `InstancePresence`
+    instance: String,
+    // participants on the other instances, by instance and event, and when they were heard
+    remote_participants: HashMap<(String, i32), (usize, Instant)>,
+    // participants this instance last announced for each event
+    announced: HashMap<i32, usize>,
+    // set once the server starts, everything is sent through it
+    publisher: Option<Publisher>,
 }
 
 impl Server {
-    pub fn new() -> Self {
+    /// Uses `Backpressure::from_env`, see `with_backpressure`
+    pub fn new(pool: PgPool) -> Self {
+        Self::with_backpressure(pool, Backpressure::from_env())
+    }
+
+    /// Loads the replay buffer saved by the last run, and the last seq it handed out, so
+    /// sequence numbers keep increasing across restarts
+    pub fn with_backpressure(pool: PgPool, backpressure: Backpressure) -> Self {
+        let load = || -> Result<(Vec<EventMessage>, i64), Error> {
+            let conn = get_conn(&pool)?;
+            let history = EventMessage::recent(&conn, REPLAY_BUFFER_SIZE as i64)?;
+            Ok((history, EventMessage::last_seq(&conn)?))
+        };
+        let (history, last_seq) = load().unwrap_or_else(|err| {
+            error!("Could not load the replay buffer: {:?}", err);
+            (Vec::new(), 0)
+        });
+        let history: VecDeque<SentMessage> = history.into_iter().map(SentMessage::from).collect();
+
         Server {
             sessions: HashMap::new(),
+            rooms: HashMap::new(),
+            pool,
+            last_seq,
+            history,
+            presence_changed: HashSet::new(),
+            presence_scheduled: false,
+            backpressure,
+            fan_out: false,
+            instance: Uuid::new_v4().to_string(),
+            remote_participants: HashMap::new(),
+            announced: HashMap::new(),
+            publisher: None,
         }
     }
 
-    fn send_message(&self, data: SerdeResult<String>) {
-        match data {
-            Ok(data) => {
-                for recipient in self.sessions.values() {
-                    match recipient.try_send(Message(data.clone())) {
-                        Err(err) => {
-                            error!("Error sending client message: {:?}", err);
-                        }
-                        _ => {}
-                    }
-                }
-            }
+    /// For running more than one instance against the same database. Broadcasts are
+    /// announced through Postgres `NOTIFY` once they are saved, rather than sent straight
+    /// back to this instance, and every instance, this one included, sends on what it hears,
+    /// so clients get every broadcast whichever instance they are connected to. Presence is
+    /// added up across instances, see `InstancePresence`
+    pub fn fan_out(mut self, fan_out: bool) -> Self {
+        self.fan_out = fan_out;
+        self
+    }
+
+    /// Participants in an event's room, see `Presence`
+    pub fn participants(&self, event_id: i32) -> usize {
+        let remote: usize = self
+            .remote_participants
+            .iter()
+            .filter(|((_, id), _)| *id == event_id)
+            .map(|(_, (participants, _))| participants)
+            .sum();
+
+        self.local_participants(event_id) + remote
+    }
+
+    fn local_participants(&self, event_id: i32) -> usize {
+        self.rooms.get(&event_id).map_or(0, |room| room.len())
+    }
+
+    // For messages that aren't replayed, so they don't get a seq
+    fn unsequenced(&self, event_id: i32, event: ServerEvent) -> Option<Message> {
+        let kind = match event {
+            ServerEvent::MessagesDropped { .. } => MessageKind::Dropped,
+            _ => MessageKind::Unsequenced,
+        };
+
+        match to_string(&MessageToClient::new(event_id, event)) {
+            Ok(text) => Some(Message { text, kind }),
             Err(err) => {
                 error!("Data did not convert to string {:?}", err);
+                None
+            }
+        }
+    }
+
+    fn presence_changed(&mut self, event_id: i32, ctx: &mut Context<Self>) {
+        self.presence_changed.insert(event_id);
+        if self.presence_scheduled {
+            return;
+        }
+
+        self.presence_scheduled = true;
+        ctx.run_later(PRESENCE_DEBOUNCE, |act, ctx| act.send_presence(ctx));
+    }
+
+    fn send_presence(&mut self, ctx: &mut Context<Self>) {
+        self.presence_scheduled = false;
+
+        for event_id in std::mem::take(&mut self.presence_changed) {
+            let local = self.local_participants(event_id);
+            if self.fan_out {
+                self.announce(event_id, local);
+            }
+            // nobody here is left to tell when the room is empty
+            if local == 0 {
+                continue;
+            }
+
+            let presence = Presence {
+                event_id,
+                participants: self.participants(event_id),
+            };
+
+            if let Some(message) = self.unsequenced(event_id, ServerEvent::Presence(presence)) {
+                self.send_message(event_id, &message, false, ctx);
+            }
+        }
+    }
+
+    // The other instances only hear about changes
+    fn announce(&mut self, event_id: i32, participants: usize) {
+        if self.announced.get(&event_id).copied().unwrap_or(0) == participants {
+            return;
+        }
+        if participants == 0 {
+            self.announced.remove(&event_id);
+        } else {
+            self.announced.insert(event_id, participants);
+        }
+
+        if let Some(publisher) = &self.publisher {
+            publisher.announce(InstancePresence {
+                instance: self.instance.clone(),
+                event_id,
+                participants,
+            });
+        }
+    }
+
+    // Forgets the instances that have gone quiet, see `PRESENCE_TTL`
+    fn expire_presence(&mut self, ctx: &mut Context<Self>) {
+        let now = Instant::now();
+        let mut expired = Vec::new();
+        self.remote_participants
+            .retain(|(_, event_id), (_, heard_at)| {
+                let fresh = now.duration_since(*heard_at) < PRESENCE_TTL;
+                if !fresh {
+                    expired.push(*event_id);
+                }
+                fresh
+            });
+
+        for event_id in expired {
+            self.presence_changed(event_id, ctx);
+        }
+    }
+
+    fn announce_all(&self) {
+        if let Some(publisher) = &self.publisher {
+            for (event_id, participants) in &self.announced {
+                publisher.announce(InstancePresence {
+                    instance: self.instance.clone(),
+                    event_id: *event_id,
+                    participants: *participants,
+                });
+            }
+        }
+    }
+
+    fn send_message(
+        &mut self,
+        event_id: i32,
+        message: &Message,
+        moderators_only: bool,
+        ctx: &mut Context<Self>,
+    ) {
+        let sessions = &self.sessions;
+        let hears = |id: &&String| {
+            sessions
+                .get(*id)
+                .is_some_and(|session| session.hears(event_id, moderators_only))
+        };
+        let ids: Vec<String> = match self.rooms.get(&event_id) {
+            Some(room) => room.iter().filter(hears).cloned().collect(),
+            None => return,
+        };
+
+        for id in ids {
+            self.deliver(&id, event_id, message, ctx);
+        }
+    }
+
+    // Never waits on a session, see `Backpressure`
+    fn deliver(&mut self, id: &str, event_id: i32, message: &Message, ctx: &mut Context<Self>) {
+        let notice = match self.sessions.get(id) {
+            Some(session) if session.unreported > 0 => {
+                let dropped = ServerEvent::MessagesDropped {
+                    dropped: session.unreported,
+                    total_dropped: session.total_dropped,
+                };
+                self.unsequenced(event_id, dropped)
+            }
+            Some(_) => None,
+            None => return,
+        };
+        let session = match self.sessions.get_mut(id) {
+            Some(session) => session,
+            None => return,
+        };
+
+        let mut res = Ok(());
+        if let Some(notice) = notice {
+            res = session.addr.try_send(notice);
+            if res.is_ok() {
+                session.unreported = 0;
+            }
+        }
+        let res = res.and_then(|_| session.addr.try_send(message.clone()));
+
+        match res {
+            Ok(()) => {
+                session.delivered += 1;
+                if session.delivered >= self.backpressure.queue_depth {
+                    session.dropped = 0;
+                }
+            }
+            Err(SendError::Full(_)) => {
+                session.unreported += 1;
+                session.dropped += 1;
+                session.delivered = 0;
+                session.total_dropped += 1;
+                if session.dropped > self.backpressure.max_dropped {
+                    warn!("Disconnecting session {}, it fell too far behind", id);
+                    session.evict.do_send(Evict);
+                    self.remove_session(id, ctx);
+                }
             }
+            // the session stopped without disconnecting
+            Err(SendError::Closed(_)) => self.remove_session(id, ctx),
+        }
+    }
+
+    fn remove_session(&mut self, id: &str, ctx: &mut Context<Self>) {
+        self.sessions.remove(id);
+
+        let mut left = Vec::new();
+        self.rooms.retain(|event_id, room| {
+            if room.remove(id) {
+                left.push(*event_id);
+            }
+            !room.is_empty()
+        });
+        for event_id in left {
+            self.presence_changed(event_id, ctx);
+        }
+    }
+
+    /// The messages for `event_id` sent after `last_seq`, or the oldest seq still kept
+    /// when some of them are gone. A `last_seq` from the future, e.g. from before the
+    /// buffer was lost, can't be trusted either. Only moderators and hosts get messages
+    /// that were for them
+    fn replay(&self, event_id: i32, role: Role, last_seq: i64) -> Result<Vec<&SentMessage>, i64> {
+        let oldest_seq = self
+            .history
+            .front()
+            .map_or(self.last_seq + 1, |message| message.seq);
+        if last_seq > self.last_seq || last_seq + 1 < oldest_seq {
+            return Err(oldest_seq);
+        }
+
+        Ok(self
+            .history
+            .iter()
+            .filter(|message| message.seq > last_seq && message.event_id == event_id)
+            .filter(|message| !message.moderators_only || role >= Role::Moderator)
+            .collect())
+    }
+
+    fn broadcast(&mut self, message: SentMessage, ctx: &mut Context<Self>) {
+        self.last_seq = message.seq;
+        self.send_message(
+            message.event_id,
+            &message.message(),
+            message.moderators_only,
+            ctx,
+        );
+
+        self.history.push_back(message);
+        if self.history.len() > REPLAY_BUFFER_SIZE {
+            self.history.pop_front();
         }
     }
 }
 
 impl Actor for Server {
     type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        let addr = ctx.address();
+        self.publisher = Some(Publisher::start(
+            self.pool.clone(),
+            addr.downgrade(),
+            self.fan_out,
+        ));
+        if self.fan_out {
+            fanout::listen(addr, self.pool.clone(), self.last_seq);
+            ctx.run_interval(PRESENCE_HEARTBEAT, |act, ctx| {
+                act.announce_all();
+                act.expire_presence(ctx);
+            });
+        }
+    }
 }
 
+/// Answered with the queue depth the session should give its mailbox
 #[derive(ActixMessage)]
-#[rtype(result = "()")]
+#[rtype(result = "usize")]
 pub struct Connect {
     pub addr: Recipient<Message>,
+    pub evict: Recipient<Evict>,
     pub id: String,
+    pub event_id: i32,
+    /// The session's role in the event, which decides whether it hears about questions
+    /// waiting on moderation
+    pub role: Role,
+    /// The last `seq` a reconnecting client saw. Whatever it missed since is sent before
+    /// anything new
+    pub last_seq: Option<i64>,
 }
 
 impl Handler<Connect> for Server {
+    type Result = usize;
+
+    fn handle(&mut self, msg: Connect, ctx: &mut Context<Self>) -> usize {
+        // replays skip the queue limit, they are already capped at REPLAY_BUFFER_SIZE
+        if let Some(last_seq) = msg.last_seq {
+            match self.replay(msg.event_id, msg.role, last_seq) {
+                Ok(messages) => {
+                    for message in messages {
+                        msg.addr.do_send(message.message());
+                    }
+                }
+                Err(oldest_seq) => {
+                    let resync = ServerEvent::ResyncRequired {
+                        oldest_seq,
+                        last_seq: self.last_seq,
+                    };
+                    if let Some(message) = self.unsequenced(msg.event_id, resync) {
+                        msg.addr.do_send(message);
+                    }
+                }
+            }
+        }
+
+        let session = Session {
+            addr: msg.addr,
+            evict: msg.evict,
+            roles: vec![(msg.event_id, msg.role)].into_iter().collect(),
+            unreported: 0,
+            dropped: 0,
+            delivered: 0,
+            total_dropped: 0,
+        };
+        self.sessions.insert(msg.id.clone(), session);
+        self.rooms.entry(msg.event_id).or_default().insert(msg.id);
+        self.presence_changed(msg.event_id, ctx);
+
+        self.backpressure.queue_depth
+    }
+}
+
+/// Adds a connected session to another event's room
+#[derive(ActixMessage)]
+#[rtype(result = "()")]
+pub struct Join {
+    pub id: String,
+    pub event_id: i32,
+    pub role: Role,
+}
+
+impl Handler<Join> for Server {
     type Result = ();
 
-    fn handle(&mut self, msg: Connect, _: &mut Context<Self>) {
-        self.sessions.insert(msg.id.clone(), msg.addr);
+    fn handle(&mut self, msg: Join, ctx: &mut Context<Self>) {
+        match self.sessions.get_mut(&msg.id) {
+            Some(session) => session.roles.insert(msg.event_id, msg.role),
+            None => return,
+        };
+
+        if self.rooms.entry(msg.event_id).or_default().insert(msg.id) {
+            self.presence_changed(msg.event_id, ctx);
+        }
     }
 }
 
@@ -82,8 +689,85 @@ pub struct Disconnect {
 impl Handler<Disconnect> for Server {
     type Result = ();
 
-    fn handle(&mut self, msg: Disconnect, _: &mut Context<Self>) {
-        self.sessions.remove(&msg.id);
+    fn handle(&mut self, msg: Disconnect, ctx: &mut Context<Self>) {
+        self.remove_session(&msg.id, ctx);
+    }
+}
+
+impl Handler<InstancePresence> for Server {
+    type Result = ();
+
+    fn handle(&mut self, msg: InstancePresence, ctx: &mut Context<Self>) {
+        // already counted
+        if msg.instance == self.instance {
+            return;
+        }
+
+        // an instance that started after this one announced hasn't heard its counts yet
+        let known = self
+            .remote_participants
+            .keys()
+            .any(|(instance, _)| *instance == msg.instance);
+        if !known {
+            self.announce_all();
+        }
+
+        let key = (msg.instance, msg.event_id);
+        if msg.participants == 0 {
+            self.remote_participants.remove(&key);
+        } else {
+            self.remote_participants
+                .insert(key, (msg.participants, Instant::now()));
+        }
+        self.presence_changed(msg.event_id, ctx);
+    }
+}
+
+/// A snapshot of an event's `Presence`
+#[derive(ActixMessage)]
+#[rtype(result = "Presence")]
+pub struct GetPresence {
+    pub event_id: i32,
+}
+
+impl Handler<GetPresence> for Server {
+    type Result = MessageResult<GetPresence>;
+
+    fn handle(&mut self, msg: GetPresence, _: &mut Context<Self>) -> Self::Result {
+        MessageResult(Presence {
+            event_id: msg.event_id,
+            participants: self.participants(msg.event_id),
+        })
+    }
+}
+
+/// `SessionStats` for the sessions in an event's room
+#[derive(ActixMessage)]
+#[rtype(result = "Vec<SessionStats>")]
+pub struct GetSessionStats {
+    pub event_id: i32,
+}
+
+impl Handler<GetSessionStats> for Server {
+    type Result = MessageResult<GetSessionStats>;
+
+    fn handle(&mut self, msg: GetSessionStats, _: &mut Context<Self>) -> Self::Result {
+        let mut stats: Vec<SessionStats> = self
+            .rooms
+            .get(&msg.event_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|id| {
+                self.sessions.get(id).map(|session| SessionStats {
+                    id: id.clone(),
+                    dropped: session.dropped,
+                    total_dropped: session.total_dropped,
+                })
+            })
+            .collect();
+        stats.sort_by(|a, b| a.id.cmp(&b.id));
+
+        MessageResult(stats)
     }
 }
 
@@ -91,6 +775,519 @@ impl Handler<MessageToClient> for Server {
     type Result = ();
 
     fn handle(&mut self, msg: MessageToClient, _: &mut Context<Self>) -> Self::Result {
-        self.send_message(to_string(&msg));
+        // comes back as `Published` once it is saved with a seq
+        match &self.publisher {
+            Some(publisher) => publisher.publish(msg),
+            None => error!("Could not publish broadcast, the server hasn't started"),
+        }
     }
-}
\ No newline at end of file
+}
+
+impl Handler<Published> for Server {
+    type Result = ();
+
+    fn handle(&mut self, msg: Published, ctx: &mut Context<Self>) -> Self::Result {
+        self.broadcast(SentMessage::from(msg.0), ctx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    use actix::prelude::{Actor, Addr, Context, Handler};
+    use diesel::{self, QueryDsl, RunQueryDsl};
+
+    use db::{
+        get_conn,
+        models::{Event, EventMessage, NewEventMessage, Question, Role},
+        new_pool,
+        schema::events,
+    };
+
+    use super::{
+        Backpressure, Connect, Disconnect, Evict, GetPresence, GetSessionStats, Message,
+        MessageKind, MessageToClient, SentMessage, Server, ServerEvent, Session, PRESENCE_DEBOUNCE,
+        PRESENCE_TTL,
+    };
+
+    // Keeps what it was sent, and only gets to read its mailbox when the test yields
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl Actor for Recorder {
+        type Context = Context<Self>;
+    }
+
+    impl Handler<Message> for Recorder {
+        type Result = ();
+
+        fn handle(&mut self, msg: Message, _: &mut Context<Self>) {
+            self.0.lock().unwrap().push(msg.text);
+        }
+    }
+
+    impl Handler<Evict> for Recorder {
+        type Result = ();
+
+        fn handle(&mut self, _: Evict, _: &mut Context<Self>) {
+            self.0.lock().unwrap().push("evicted".to_string());
+        }
+    }
+
+    // (seq, event_id) pairs, with the seq as the text
+    fn server_with(history: Vec<(i64, i32)>) -> Server {
+        Server {
+            sessions: HashMap::new(),
+            rooms: HashMap::new(),
+            pool: new_pool(),
+            last_seq: history.last().map_or(0, |(seq, _)| *seq),
+            history: history
+                .into_iter()
+                .map(|(seq, event_id)| SentMessage {
+                    seq,
+                    event_id,
+                    text: seq.to_string(),
+                    moderators_only: false,
+                })
+                .collect(),
+            presence_changed: HashSet::new(),
+            presence_scheduled: false,
+            backpressure: Backpressure::default(),
+            fan_out: false,
+            instance: "local".to_string(),
+            remote_participants: HashMap::new(),
+            announced: HashMap::new(),
+            publisher: None,
+        }
+    }
+
+    // The texts `replay` picks, which `server_with` made the seqs
+    fn replayed(
+        server: &Server,
+        event_id: i32,
+        role: Role,
+        last_seq: i64,
+    ) -> Result<Vec<&str>, i64> {
+        let messages = server.replay(event_id, role, last_seq)?;
+        Ok(messages
+            .iter()
+            .map(|message| message.text.as_str())
+            .collect())
+    }
+
+    fn message(text: &str) -> Message {
+        Message {
+            text: text.to_string(),
+            kind: MessageKind::Unsequenced,
+        }
+    }
+
+    #[test]
+    fn test_replay() {
+        let server = server_with(vec![(5, 1), (6, 2), (7, 1), (8, 1)]);
+        assert_eq!(
+            replayed(&server, 1, Role::Participant, 6),
+            Ok(vec!["7", "8"])
+        );
+        assert_eq!(
+            replayed(&server, 1, Role::Participant, 4),
+            Ok(vec!["5", "7", "8"])
+        );
+        assert_eq!(replayed(&server, 2, Role::Participant, 4), Ok(vec!["6"]));
+        assert_eq!(replayed(&server, 1, Role::Participant, 8), Ok(vec![]));
+
+        // 4 and before are gone, and 9 hasn't happened yet
+        assert_eq!(replayed(&server, 1, Role::Participant, 3), Err(5));
+        assert_eq!(replayed(&server, 1, Role::Participant, 9), Err(5));
+
+        let server = server_with(vec![]);
+        assert_eq!(replayed(&server, 1, Role::Participant, 0), Ok(vec![]));
+        assert_eq!(replayed(&server, 1, Role::Participant, 1), Err(1));
+
+        // moderators also get what was only for them
+        let mut server = server_with(vec![(5, 1), (6, 1)]);
+        server.history[0].moderators_only = true;
+        assert_eq!(replayed(&server, 1, Role::Participant, 4), Ok(vec!["6"]));
+        assert_eq!(replayed(&server, 1, Role::Moderator, 4), Ok(vec!["5", "6"]));
+    }
+
+    // A session in event 1 that only reads its mailbox of `capacity` when the test yields
+    fn slow_session(server: &mut Server, capacity: usize) -> Arc<Mutex<Vec<String>>> {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let addr = Recorder::create({
+            let received = received.clone();
+            move |ctx| {
+                ctx.set_mailbox_capacity(capacity);
+                Recorder(received)
+            }
+        });
+        let session = Session {
+            addr: addr.clone().recipient(),
+            evict: addr.recipient(),
+            roles: HashMap::new(),
+            unreported: 0,
+            dropped: 0,
+            delivered: 0,
+            total_dropped: 0,
+        };
+        server.sessions.insert("slow".to_string(), session);
+        server
+            .rooms
+            .entry(1)
+            .or_default()
+            .insert("slow".to_string());
+
+        received
+    }
+
+    #[actix_rt::test]
+    async fn test_slow_sessions_drop_messages() {
+        let mut server = server_with(vec![]);
+        server.backpressure = Backpressure {
+            queue_depth: 2,
+            max_dropped: 2,
+        };
+        let mut ctx = Context::<Server>::new();
+        let received = slow_session(&mut server, 2);
+
+        let stats = |server: &mut Server| {
+            let stats = server.handle(GetSessionStats { event_id: 1 }, &mut Context::new());
+            stats.0
+        };
+
+        // the third doesn't fit until the session catches up
+        for text in &["1", "2", "3"] {
+            server.send_message(1, &message(text), false, &mut ctx);
+        }
+        let slow = &stats(&mut server)[0];
+        assert_eq!((slow.dropped, slow.total_dropped), (1, 1));
+        actix_rt::time::sleep(Duration::from_millis(10)).await;
+
+        // it hears about the gap before the next message
+        server.send_message(1, &message("4"), false, &mut ctx);
+        actix_rt::time::sleep(Duration::from_millis(10)).await;
+        {
+            let received = received.lock().unwrap();
+            assert_eq!(received.len(), 4);
+            assert_eq!(received[..2], ["1", "2"]);
+            let notice: MessageToClient = serde_json::from_str(&received[2]).unwrap();
+            // so a client that records it can't resume from past what it missed
+            assert_eq!(notice.seq, None);
+            assert!(matches!(
+                notice.event,
+                ServerEvent::MessagesDropped {
+                    dropped: 1,
+                    total_dropped: 1
+                }
+            ));
+            assert_eq!(received[3], "4");
+        }
+        // one message isn't a queue's worth, so it hasn't caught up yet
+        let slow = &stats(&mut server)[0];
+        assert_eq!((slow.dropped, slow.total_dropped), (1, 1));
+
+        // the next one is, and then it is let go once it drops more than max_dropped
+        for text in &["5", "6", "7", "8", "9"] {
+            server.send_message(1, &message(text), false, &mut ctx);
+        }
+        assert!(server.sessions.is_empty());
+        assert_eq!(server.participants(1), 0);
+        assert!(stats(&mut server).is_empty());
+
+        actix_rt::time::sleep(Duration::from_millis(10)).await;
+        let received = received.lock().unwrap();
+        assert_eq!(received[4..], ["5", "6", "evicted"]);
+    }
+
+    #[actix_rt::test]
+    async fn test_sessions_that_never_catch_up_are_evicted() {
+        let mut server = server_with(vec![]);
+        server.backpressure = Backpressure {
+            queue_depth: 2,
+            max_dropped: 3,
+        };
+        let mut ctx = Context::<Server>::new();
+        let received = slow_session(&mut server, 2);
+
+        // it reads between bursts, and every notice gets through, but it never has room for
+        // more than one message after it
+        let mut rounds = 0;
+        while !server.sessions.is_empty() {
+            assert!(rounds < 5, "the session was never evicted");
+            for text in &["a", "b", "c"] {
+                server.send_message(1, &message(text), false, &mut ctx);
+            }
+            actix_rt::time::sleep(Duration::from_millis(10)).await;
+            rounds += 1;
+        }
+
+        assert_eq!(rounds, 3);
+        let received = received.lock().unwrap();
+        assert_eq!(received.last().unwrap(), "evicted");
+    }
+
+    #[test]
+    fn test_new_picks_up_the_saved_sequence() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+
+        let last_seq = EventMessage::last_seq(&conn).unwrap();
+        for seq in &[last_seq + 1, last_seq + 2] {
+            EventMessage::create(
+                &conn,
+                NewEventMessage {
+                    seq: *seq,
+                    event_id: event.id,
+                    message: seq.to_string(),
+                    moderators_only: false,
+                },
+            )
+            .unwrap();
+        }
+
+        // other tests may have carried on since
+        let server = Server::new(pool.clone());
+        assert!(server.last_seq >= last_seq + 2);
+        assert_eq!(
+            replayed(&server, event.id, Role::Participant, last_seq + 1),
+            Ok(vec![(last_seq + 2).to_string().as_str()])
+        );
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+    }
+
+    #[test]
+    fn test_seqs_are_not_handed_out_again() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+        let other_event = Event::create(&conn, "Another event", false).unwrap();
+
+        let publish = |event_id| {
+            EventMessage::publish(
+                &conn,
+                None,
+                event_id,
+                false,
+                1000,
+                |seq| Ok(seq.to_string()),
+            )
+            .unwrap()
+        };
+        let published = publish(event.id);
+
+        // the newest messages go with their event
+        diesel::delete(events::table.find(event.id))
+            .execute(&conn)
+            .unwrap();
+        assert!(publish(other_event.id).seq > published.seq);
+        assert!(Server::new(pool.clone()).last_seq > published.seq);
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_broadcasts_are_saved_before_they_are_sent() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+        let question = Question::create(&conn, event.id, None, &"Saved".to_string()).unwrap();
+
+        let server = Server::new(pool.clone()).start();
+
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let addr = Recorder(received.clone()).start();
+        server
+            .send(Connect {
+                addr: addr.clone().recipient(),
+                evict: addr.recipient(),
+                id: "saved".to_string(),
+                event_id: event.id,
+                role: Role::Participant,
+                last_seq: None,
+            })
+            .await
+            .unwrap();
+
+        server.do_send(MessageToClient::new(
+            event.id,
+            ServerEvent::NewQuestion(question),
+        ));
+
+        let mut sent = None;
+        for _ in 0..40 {
+            sent = received
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|text| serde_json::from_str::<MessageToClient>(text).unwrap())
+                .find(|msg| matches!(msg.event, ServerEvent::NewQuestion(_)));
+            if sent.is_some() {
+                break;
+            }
+            actix_rt::time::sleep(Duration::from_millis(50)).await;
+        }
+        let sent = sent.expect("the broadcast was never sent").seq.unwrap();
+
+        // so a restart straight after carries on past it
+        let saved = EventMessage::after(&conn, sent - 1, 1).unwrap();
+        assert_eq!(saved[0].seq, sent);
+        assert!(Server::new(pool.clone()).last_seq >= sent);
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_fan_out_reaches_every_instance() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+        let question = Question::create(&conn, event.id, None, &"Fanned out".to_string()).unwrap();
+
+        let first = Server::new(pool.clone()).fan_out(true).start();
+        let second = Server::new(pool.clone()).fan_out(true).start();
+
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let addr = Recorder(received.clone()).start();
+        second
+            .send(Connect {
+                addr: addr.clone().recipient(),
+                evict: addr.recipient(),
+                id: "elsewhere".to_string(),
+                event_id: event.id,
+                role: Role::Participant,
+                last_seq: None,
+            })
+            .await
+            .unwrap();
+
+        first.do_send(MessageToClient::new(
+            event.id,
+            ServerEvent::NewQuestion(question),
+        ));
+
+        // presence from the second instance may arrive too
+        let mut published = None;
+        for _ in 0..40 {
+            published = received
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|text| serde_json::from_str::<MessageToClient>(text).unwrap())
+                .find(|msg| matches!(msg.event, ServerEvent::NewQuestion(_)));
+            if published.is_some() {
+                break;
+            }
+            actix_rt::time::sleep(Duration::from_millis(50)).await;
+        }
+        let published = published
+            .expect("the broadcast never reached the other instance")
+            .seq
+            .unwrap();
+
+        // saved under the seq it was sent with, for replays on either instance
+        let saved = EventMessage::after(&conn, published - 1, 1).unwrap();
+        assert_eq!(saved[0].seq, published);
+        assert_eq!(saved[0].event_id, event.id);
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+    }
+
+    // Long enough for a heartbeat to go round
+    async fn wait_for_participants(server: &Addr<Server>, event_id: i32, participants: usize) {
+        for _ in 0..200 {
+            let presence = server.send(GetPresence { event_id }).await.unwrap();
+            if presence.participants == participants {
+                return;
+            }
+            actix_rt::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("presence never reached {}", participants);
+    }
+
+    #[actix_rt::test]
+    async fn test_fan_out_adds_up_presence() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+
+        let first = Server::new(pool.clone()).fan_out(true).start();
+        let second = Server::new(pool.clone()).fan_out(true).start();
+
+        for (server, id) in [(&first, "here"), (&second, "elsewhere")] {
+            let addr = Recorder(Arc::new(Mutex::new(Vec::new()))).start();
+            server
+                .send(Connect {
+                    addr: addr.clone().recipient(),
+                    evict: addr.recipient(),
+                    id: id.to_string(),
+                    event_id: event.id,
+                    role: Role::Participant,
+                    last_seq: None,
+                })
+                .await
+                .unwrap();
+        }
+
+        wait_for_participants(&first, event.id, 2).await;
+        wait_for_participants(&second, event.id, 2).await;
+
+        second.do_send(Disconnect {
+            id: "elsewhere".to_string(),
+        });
+        wait_for_participants(&first, event.id, 1).await;
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_fan_out_reaches_instances_without_participants() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+
+        let first = Server::new(pool.clone()).fan_out(true).start();
+        let addr = Recorder(Arc::new(Mutex::new(Vec::new()))).start();
+        first
+            .send(Connect {
+                addr: addr.clone().recipient(),
+                evict: addr.recipient(),
+                id: "here".to_string(),
+                event_id: event.id,
+                role: Role::Participant,
+                last_seq: None,
+            })
+            .await
+            .unwrap();
+        wait_for_participants(&first, event.id, 1).await;
+        actix_rt::time::sleep(PRESENCE_DEBOUNCE * 2).await;
+
+        // started after the first announced, and never announces anything itself
+        let second = Server::new(pool.clone()).fan_out(true).start();
+        wait_for_participants(&second, event.id, 1).await;
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_quiet_instances_are_forgotten() {
+        let mut server = server_with(vec![]);
+        let mut ctx = Context::<Server>::new();
+
+        let long_ago = Instant::now().checked_sub(PRESENCE_TTL).unwrap();
+        server
+            .remote_participants
+            .insert(("crashed".to_string(), 1), (3, long_ago));
+        server
+            .remote_participants
+            .insert(("running".to_string(), 1), (2, Instant::now()));
+        assert_eq!(server.participants(1), 5);
+
+        server.expire_presence(&mut ctx);
+        assert_eq!(server.participants(1), 2);
+        assert!(server.presence_changed.contains(&1));
+    }
+}
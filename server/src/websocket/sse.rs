@@ -0,0 +1,529 @@
+use std::convert::Infallible;
+use std::time::Duration;
+
+use uuid::Uuid;
+
+use actix::{
+    fut,
+    prelude::{Actor, Addr, Context, Handler},
+    ActorContext, ActorFutureExt, AsyncContext, ContextFutureSpawner, Running, WrapFuture,
+};
+use actix_web::{
+    http::header::{HeaderName, CACHE_CONTROL},
+    web::{Bytes, Data, Query},
+    HttpRequest, HttpResponse,
+};
+use futures::{
+    channel::mpsc::{channel, Sender},
+    StreamExt,
+};
+
+use db::{models::Role, PgPool};
+use errors::Error;
+
+use super::{
+    authenticate, Backpressure, Connect, Disconnect, Evict, Message, MessageKind, Server, WsParams,
+    REPLAY_BUFFER_SIZE,
+};
+use crate::auth::{CurrentUser, TokenKey};
+
+/// Comments are sent this often so proxies don't close a quiet stream
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Header browsers resend with the last `id` they saw when they reconnect on their own
+const LAST_EVENT_ID: &str = "last-event-id";
+
+/// The server sent events counterpart of `WebSocketSession`, for clients that can't open a
+/// websocket. It gets the same messages, written to a `text/event-stream` response
+pub struct SseSession {
+    id: String,
+    event_id: i32,
+    role: Role,
+    last_seq: Option<i64>,
+    server_addr: Addr<Server>,
+    sender: Sender<Bytes>,
+}
+
+impl SseSession {
+    fn send(&mut self, chunk: String, ctx: &mut <Self as Actor>::Context) {
+        // Either the response was dropped, so the client is gone, or it has a queue's worth
+        // of chunks it hasn't read. The mailbox drains into the response as fast as it can, so
+        // this is where a slow client shows up. Ending the stream makes the browser reconnect
+        // with `Last-Event-ID` and get what it missed replayed
+        if self.sender.try_send(Bytes::from(chunk)).is_err() {
+            ctx.stop();
+        }
+    }
+
+    // Closing the channel ends the response straight away, and nothing still in the mailbox
+    // can be written after it
+    fn end(&mut self, ctx: &mut <Self as Actor>::Context) {
+        self.sender.close_channel();
+        ctx.stop();
+    }
+}
+
+impl Actor for SseSession {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        // sent straight away so the response starts, rather than waiting on the first message
+        self.send(": connected\n\n".to_string(), ctx);
+        ctx.run_interval(KEEP_ALIVE_INTERVAL, |act, ctx| {
+            act.send(": keep-alive\n\n".to_string(), ctx);
+        });
+
+        let session_addr = ctx.address();
+        self.server_addr
+            .send(Connect {
+                addr: session_addr.clone().recipient(),
+                evict: session_addr.recipient(),
+                id: self.id.clone(),
+                event_id: self.event_id,
+                role: self.role,
+                last_seq: self.last_seq,
+            })
+            .into_actor(self)
+            .then(|res, _act, ctx| {
+                match res {
+                    Ok(queue_depth) => ctx.set_mailbox_capacity(queue_depth),
+                    Err(_) => ctx.stop(),
+                }
+                fut::ready(())
+            })
+            .wait(ctx);
+    }
+
+    fn stopping(&mut self, _: &mut Self::Context) -> Running {
+        self.server_addr.do_send(Disconnect {
+            id: self.id.clone(),
+        });
+        Running::Stop
+    }
+}
+
+impl Handler<Message> for SseSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: Message, ctx: &mut Self::Context) {
+        match to_event(&msg) {
+            Some(chunk) => self.send(chunk, ctx),
+            None => self.end(ctx),
+        }
+    }
+}
+
+// Ending the response makes the browser reconnect with `Last-Event-ID`
+impl Handler<Evict> for SseSession {
+    type Result = ();
+
+    fn handle(&mut self, _: Evict, ctx: &mut Self::Context) {
+        ctx.stop();
+    }
+}
+
+// A replayed message's seq is its event id, so a reconnecting browser resumes where it left
+// off. `None` when the stream should end instead: after messages were dropped, the next one's
+// id would move Last-Event-ID past them, so the browser is made to reconnect and have them
+// replayed
+fn to_event(msg: &Message) -> Option<String> {
+    match msg.kind {
+        MessageKind::Sequenced(seq) => Some(format!("id: {}\ndata: {}\n\n", seq, msg.text)),
+        MessageKind::Unsequenced => Some(format!("data: {}\n\n", msg.text)),
+        MessageKind::Dropped => None,
+    }
+}
+
+// Room for a whole replay on top of the mailbox, since replays skip its limit
+fn stream_capacity(backpressure: &Backpressure) -> usize {
+    backpressure.queue_depth + REPLAY_BUFFER_SIZE
+}
+
+/// Takes the same parameters and authentication as the websocket, with `Last-Event-ID`
+/// taking the place of `last_seq` when it is sent. A token only lasts long enough to open the
+/// stream, so when a stream opened with one drops, the client should close its `EventSource`,
+/// ask for a new token and open a new stream with `last_seq`, rather than let the browser
+/// reconnect with the old url
+pub async fn event_stream(
+    req: HttpRequest,
+    pool: Data<PgPool>,
+    server_addr: Data<Addr<Server>>,
+    key: Data<TokenKey>,
+    user: Option<CurrentUser>,
+    params: Query<WsParams>,
+) -> Result<HttpResponse, Error> {
+    let event_id = params
+        .event_id
+        .ok_or_else(|| Error::invalid_field("event_id", "required", "event_id is required"))?;
+
+    let last_seq = match req.headers().get(LAST_EVENT_ID) {
+        Some(value) => {
+            let last_seq = value.to_str().ok().and_then(|value| value.parse().ok());
+            Some(last_seq.ok_or_else(|| {
+                Error::invalid_field(
+                    "Last-Event-ID",
+                    "invalid",
+                    "Last-Event-ID must be a sequence number",
+                )
+            })?)
+        }
+        None => params.last_seq,
+    };
+
+    let (_, role) = authenticate(&pool, &key, user, params.token.as_deref(), event_id).await?;
+
+    let (sender, receiver) = channel(stream_capacity(&Backpressure::from_env()));
+    SseSession {
+        id: Uuid::new_v4().to_string(),
+        event_id,
+        role,
+        last_seq,
+        server_addr: server_addr.get_ref().clone(),
+        sender,
+    }
+    .start();
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((CACHE_CONTROL, "no-cache"))
+        // nginx buffers responses by default, which holds events back
+        .insert_header((HeaderName::from_static("x-accel-buffering"), "no"))
+        .streaming(receiver.map(Ok::<_, Infallible>)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use actix::Actor;
+    use actix_web::{
+        http::header::CONTENT_TYPE,
+        web::{Bytes, BytesMut},
+    };
+    use awc::error::PayloadError;
+    use chrono::Utc;
+    use diesel::{self, RunQueryDsl};
+    use futures::{channel::mpsc::channel, Stream, StreamExt};
+    use serde_json::json;
+    use uuid::Uuid;
+
+    use db::{
+        get_conn,
+        models::{Event, EventMessage, Question, Role, User},
+        new_pool,
+        schema::{events, users},
+    };
+    use errors::ErrorResponse;
+
+    use super::{stream_capacity, to_event, SseSession};
+    use crate::auth::{CurrentUser, TokenKey};
+    use crate::tests;
+    use crate::websocket::fanout::Published;
+    use crate::websocket::{
+        Backpressure, Message, MessageKind, MessageToClient, Server, ServerEvent,
+    };
+
+    // Reads the stream until `count` events with data have arrived, skipping presence
+    async fn read_events<S>(stream: &mut S, count: usize) -> Vec<(i64, MessageToClient)>
+    where
+        S: Stream<Item = Result<Bytes, PayloadError>> + Unpin,
+    {
+        let mut buffer = BytesMut::new();
+        let mut events = Vec::new();
+
+        while events.len() < count {
+            buffer.extend_from_slice(&stream.next().await.unwrap().unwrap());
+
+            while let Some(end) = buffer.windows(2).position(|window| window == b"\n\n") {
+                let chunk = buffer.split_to(end + 2);
+                let chunk = String::from_utf8(chunk.to_vec()).unwrap();
+
+                let mut id = None;
+                let mut data = None;
+                for line in chunk.lines() {
+                    if let Some(value) = line.strip_prefix("id: ") {
+                        id = Some(value.parse().unwrap());
+                    } else if let Some(value) = line.strip_prefix("data: ") {
+                        data = Some(serde_json::from_str::<MessageToClient>(value).unwrap());
+                    }
+                }
+
+                match (id, data) {
+                    (_, Some(msg)) if matches!(msg.event, ServerEvent::Presence(_)) => {}
+                    (Some(id), Some(msg)) => events.push((id, msg)),
+                    _ => {}
+                }
+            }
+        }
+
+        events
+    }
+
+    #[test]
+    fn test_to_event() {
+        let message = |kind| Message {
+            text: "{}".to_string(),
+            kind,
+        };
+
+        // only what is replayed moves Last-Event-ID
+        assert_eq!(
+            to_event(&message(MessageKind::Sequenced(4))),
+            Some("id: 4\ndata: {}\n\n".to_string())
+        );
+        assert_eq!(
+            to_event(&message(MessageKind::Unsequenced)),
+            Some("data: {}\n\n".to_string())
+        );
+        assert_eq!(to_event(&message(MessageKind::Dropped)), None);
+    }
+
+    #[actix_rt::test]
+    async fn test_event_stream() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+
+        let srv = tests::get_test_server();
+        let post_question = |body: &'static str| {
+            srv.post(format!("/api/events/{}/questions", event.id))
+                .send_json(&json!({ "body": body }))
+        };
+
+        let mut stream = srv
+            .get(format!(
+                "/api/events/stream?event_id={}&token={}",
+                event.id,
+                tests::ws_token()
+            ))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(stream.status().as_u16(), 200);
+        assert_eq!(
+            stream.headers().get(CONTENT_TYPE).unwrap(),
+            "text/event-stream"
+        );
+
+        // the stream opens with a comment
+        let first = stream.next().await.unwrap().unwrap();
+        assert!(first.starts_with(b":"));
+
+        post_question("Streamed").await.unwrap();
+        let received = read_events(&mut stream, 1).await;
+        let (seen, msg) = &received[0];
+        assert_eq!(Some(*seen), msg.seq);
+        let question = match &msg.event {
+            ServerEvent::NewQuestion(question) => question,
+            event => panic!("expected newquestion, got {:?}", event),
+        };
+        assert_eq!(question.body, "Streamed");
+        drop(stream);
+
+        post_question("Missed").await.unwrap();
+
+        // as a browser would when reconnecting
+        let mut stream = srv
+            .get(format!(
+                "/api/events/stream?event_id={}&token={}",
+                event.id,
+                tests::ws_token()
+            ))
+            .insert_header(("Last-Event-ID", seen.to_string()))
+            .send()
+            .await
+            .unwrap();
+        let received = read_events(&mut stream, 1).await;
+        assert!(received[0].0 > *seen);
+        let question = match &received[0].1.event {
+            ServerEvent::NewQuestion(question) => question,
+            event => panic!("expected newquestion, got {:?}", event),
+        };
+        assert_eq!(question.body, "Missed");
+        drop(stream);
+
+        srv.stop().await;
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_dropped_messages_are_replayed_after_reconnecting() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+        let question = Question::create(&conn, event.id, None, &"Voted on".to_string()).unwrap();
+
+        let backpressure = Backpressure {
+            queue_depth: 2,
+            max_dropped: 100,
+        };
+        let server = Server::with_backpressure(pool.clone(), backpressure).start();
+        let open = |last_seq| {
+            let (sender, receiver) = channel(stream_capacity(&backpressure));
+            SseSession {
+                id: Uuid::new_v4().to_string(),
+                event_id: event.id,
+                role: Role::Participant,
+                last_seq,
+                server_addr: server.clone(),
+                sender,
+            }
+            .start();
+            receiver.map(Ok::<_, PayloadError>)
+        };
+        let vote = || MessageToClient::new(event.id, ServerEvent::VoteChanged(question.clone()));
+
+        let stream = open(None);
+        actix_rt::time::sleep(Duration::from_millis(10)).await;
+
+        // saved first and handed to the server in one go, so most don't fit in the session's
+        // queue
+        let published: Vec<EventMessage> = (0..5)
+            .map(|_| {
+                EventMessage::publish(&conn, None, event.id, false, 1000, |seq| {
+                    Ok(serde_json::to_string(&MessageToClient {
+                        seq: Some(seq),
+                        ..vote()
+                    })
+                    .unwrap())
+                })
+                .unwrap()
+            })
+            .collect();
+        for message in published {
+            server.do_send(Published(message));
+        }
+        actix_rt::time::sleep(Duration::from_millis(10)).await;
+
+        // this one comes after the notice about the dropped ones, which ends the stream
+        server.do_send(vote());
+        let chunks: Vec<Bytes> = stream.map(Result::unwrap).collect().await;
+        let mut seen: Vec<i64> = chunks
+            .iter()
+            .flat_map(|chunk| {
+                let chunk = String::from_utf8(chunk.to_vec()).unwrap();
+                let ids: Vec<i64> = chunk
+                    .lines()
+                    .filter_map(|line| line.strip_prefix("id: "))
+                    .map(|id| id.parse().unwrap())
+                    .collect();
+                ids
+            })
+            .collect();
+        assert!(!seen.is_empty() && seen.len() < 6);
+
+        // as a browser would, with the last id it saw
+        let last_event_id = *seen.last().unwrap();
+        let mut stream = open(Some(last_event_id));
+        let replayed = read_events(&mut stream, 6 - seen.len()).await;
+        seen.extend(replayed.iter().map(|(id, _)| *id));
+
+        let first = seen[0];
+        assert_eq!(seen, (first..first + 6).collect::<Vec<i64>>());
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_replays_longer_than_the_queue_fit_in_the_stream() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+        let question = Question::create(&conn, event.id, None, &"Voted on".to_string()).unwrap();
+
+        let backpressure = Backpressure {
+            queue_depth: 2,
+            max_dropped: 100,
+        };
+        let server = Server::with_backpressure(pool.clone(), backpressure).start();
+        let vote = || MessageToClient::new(event.id, ServerEvent::VoteChanged(question.clone()));
+
+        // missed while the client was away
+        let published: Vec<EventMessage> = (0..5)
+            .map(|_| {
+                EventMessage::publish(&conn, None, event.id, false, 1000, |seq| {
+                    Ok(serde_json::to_string(&MessageToClient {
+                        seq: Some(seq),
+                        ..vote()
+                    })
+                    .unwrap())
+                })
+                .unwrap()
+            })
+            .collect();
+        let first = published[0].seq;
+        for message in published {
+            server.do_send(Published(message));
+        }
+
+        let (sender, receiver) = channel(stream_capacity(&backpressure));
+        SseSession {
+            id: Uuid::new_v4().to_string(),
+            event_id: event.id,
+            role: Role::Participant,
+            last_seq: Some(first - 1),
+            server_addr: server.clone(),
+            sender,
+        }
+        .start();
+
+        let mut stream = receiver.map(Ok::<_, PayloadError>);
+        let replayed = read_events(&mut stream, 5).await;
+        let seen: Vec<i64> = replayed.iter().map(|(id, _)| *id).collect();
+        assert_eq!(seen, (first..first + 5).collect::<Vec<i64>>());
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_reconnecting_needs_a_fresh_token() {
+        let pool = new_pool();
+        let conn = get_conn(&pool).unwrap();
+        let event = Event::create(&conn, "An event", false).unwrap();
+        let user =
+            User::create(&conn, &format!("participant-{}", event.id), "correct horse").unwrap();
+
+        let expired = TokenKey::new(tests::SECRET_KEY).sign(
+            CurrentUser { id: user.id },
+            Utc::now() - chrono::Duration::seconds(1),
+        );
+
+        let srv = tests::get_test_server();
+        let open = |token: String| {
+            srv.get(format!(
+                "/api/events/stream?event_id={}&token={}",
+                event.id, token
+            ))
+            .insert_header(("Last-Event-ID", "0"))
+            .send()
+        };
+
+        // the browser reconnecting on its own with the url the stream was opened with
+        let res = open(expired).await.unwrap();
+        assert_eq!(res.status().as_u16(), 401);
+
+        let res = open(tests::token_for(user.id)).await.unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        drop(res);
+
+        srv.stop().await;
+
+        diesel::delete(events::dsl::events).execute(&conn).unwrap();
+        diesel::delete(users::dsl::users).execute(&conn).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_event_stream_params() {
+        let res: (u16, ErrorResponse) = tests::test_get("/api/events/stream").await;
+        assert_eq!(res.0, 400);
+        assert_eq!(res.1.errors, vec!["event_id is required"]);
+
+        let res: (u16, ErrorResponse) = tests::test_get("/api/events/stream?event_id=0").await;
+        assert_eq!(res.0, 401);
+
+        let route = format!("/api/events/stream?event_id=0&token={}", tests::ws_token());
+        let res: (u16, ErrorResponse) = tests::test_get(&route).await;
+        assert_eq!(res.0, 404);
+    }
+}